@@ -0,0 +1,3 @@
+pub mod rpn;
+pub mod text;
+pub mod tic_tac_toe;
@@ -1,23 +1,28 @@
-use std::fs;
+use exercices::text::{self, TextStats};
 
 pub fn run() {
-    let contents = fs::read_to_string("src/texts/Dickinson.txt")
-        .expect("Something went wrong reading the file");
+    let contents =
+        text::read_input(text::DEFAULT_INPUT).expect("Something went wrong reading the file");
 
-    // println!("{}", contents);
-    let mut result = (0, "");
+    // Words without their punctuation, lengths as a reader counts them
+    let mut stats = TextStats::default();
+    stats.add(&contents);
 
-    // Imperative:
-    for word in contents.split_whitespace() {
-        // println!("{}", word);
-        if word.len() > result.0 {
-            result.0 = word.len();
-            result.1 = word;
-        }
+    let longest = stats.longest();
+    let words: Vec<String> = longest
+        .iter()
+        .map(|entry| format!("'{}'", entry.word))
+        .collect();
+    match longest.first() {
+        Some(first) if longest.len() > 1 => println!(
+            "Longest words were {} and have {} characters",
+            words.join(", "),
+            first.length
+        ),
+        Some(first) => println!(
+            "Longest word was {} and has {} characters",
+            words[0], first.length
+        ),
+        None => println!("There were no words"),
     }
-
-    println!(
-        "Longest word was '{}' and has {} characters",
-        result.1, result.0
-    );
 }
@@ -1,10 +1,21 @@
 //
 mod longest_word;
-mod rpn;
-mod tic_tac_toe;
+
+use exercices::{rpn, text, tic_tac_toe};
+use std::env;
+use std::process;
 
 fn main() {
-    longest_word::run();
-    rpn::run();
-    tic_tac_toe::run();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("rpn") => process::exit(rpn::main(&args[1..])),
+        Some("text") => process::exit(text::main(&args[1..])),
+        Some("ttt") => process::exit(tic_tac_toe::main(&args[1..])),
+        _ => {
+            longest_word::run();
+            rpn::run(&rpn::Config::default());
+            tic_tac_toe::run(&tic_tac_toe::Config::default());
+        }
+    }
 }
@@ -1,81 +1,345 @@
-use std::fs;
+mod calculator;
+mod cli;
+mod compile;
+mod environment;
+mod errors;
+mod infix;
+mod number;
+mod ops;
+mod output;
+mod tokenizer;
+mod value;
+pub use calculator::Calculator;
+pub use cli::main;
+pub use compile::{compile, Expr, Program};
+pub use environment::Environment;
+pub use errors::{Diagnostic, RpnError};
+pub use infix::{to_infix, to_rpn};
+pub use num_rational::BigRational;
+pub use number::Number;
+pub use ops::{BinaryOp, Op, StackOp, UnaryOp};
+pub use output::{Format, Record};
+pub use tokenizer::tokenize;
+pub use value::Value;
 
-pub fn run() {
-    let contents =
-        fs::read_to_string("src/texts/rpn.txt").expect("Something went wrong reading the file");
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
 
-    // Set up stack
-    let mut stack: Vec<i32> = Vec::new();
+/// Number of values the stack may hold unless configured otherwise, enough
+/// for any line written by hand.
+pub const DEFAULT_STACK_LIMIT: usize = 1024;
 
-    for (e, line) in contents.split('\n').enumerate() {
-        // println!("Line {}: {}", e + 1, line);
+/// Lines the REPL can undo.
+pub const UNDO_LIMIT: usize = 100;
 
-        // Check if line contains at least two operands
-        if !contains_two_operands(line) {
-            println!("Line {}: not enough operands, exiting", e);
-            continue;
+/// The sample input shipped with the exercise, found regardless of the
+/// working directory.
+pub const DEFAULT_INPUT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/texts/rpn.txt");
+
+/// Which number type lines are evaluated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Integers and floats, integer overflow is reported as an error.
+    Checked,
+    /// Arbitrary precision rationals.
+    Exact,
+}
+
+/// Settings shared by the batch runner and the REPL.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub mode: Mode,
+    /// File of definitions to load before evaluating anything else.
+    pub prelude: Option<String>,
+    pub stack_limit: usize,
+    /// Show each line as infix next to its result.
+    pub explain: bool,
+    /// Files to evaluate in order, `-` reads stdin.
+    pub inputs: Vec<String>,
+    pub format: Format,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            mode: Mode::Checked,
+            prelude: None,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            explain: false,
+            inputs: vec![DEFAULT_INPUT.to_string()],
+            format: Format::Text,
         }
+    }
+}
 
-        // Process next char, catch anything that's not an operator or operand
-        for expr in line.split_whitespace() {
-            if stack.len() > 10 {
-                println!("Line {}: stack capacity reached, exiting", e);
-                break;
-            }
-            match expr.parse::<i32>().is_ok() {
-                true => stack.push(expr.parse().unwrap()),
-                false => {
-                    // * Operator
-                    if stack.len() < 2 {
-                        println!("Line {}: not enough operands", e);
-                        break;
-                    } else {
-                        perform_op(&mut stack, expr);
-                    }
-                }
-            }
+/// Evaluates every line of the configured inputs, after loading the
+/// definitions in the prelude if one is configured. Words and variables
+/// carry over from one input to the next.
+///
+/// Returns `false` if any line, or the prelude, failed.
+pub fn run(config: &Config) -> bool {
+    match config.mode {
+        Mode::Checked => run_with::<Value>(config),
+        Mode::Exact => run_with::<BigRational>(config),
+    }
+}
+
+fn run_with<N: Number>(config: &Config) -> bool {
+    let mut calculator = match setup::<N>(config) {
+        Ok(calculator) => calculator,
+        Err(message) => {
+            eprintln!("{}", message);
+            return false;
         }
+    };
 
-        check_and_return_results(&mut stack, e);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let stderr = io::stderr();
+    let mut diagnostics = stderr.lock();
+    if let Err(err) = output::write_header(config.format, &mut out) {
+        eprintln!("{}", err);
+        return false;
     }
+
+    let mut ok = true;
+    for input in &config.inputs {
+        // Only name the input when there is more than one to tell apart
+        let prefix = if config.inputs.len() > 1 {
+            format!("{}: ", input)
+        } else {
+            String::new()
+        };
+        let result = match open_input(input) {
+            Ok(reader) => print_results(
+                reader,
+                input,
+                &mut calculator,
+                config,
+                &prefix,
+                &mut out,
+                &mut diagnostics,
+            ),
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(all_passed) => ok &= all_passed,
+            Err(err) => {
+                eprintln!("{}: {}", input, err);
+                ok = false;
+            }
+        }
+    }
+    ok
 }
 
-fn check_and_return_results(stack: &mut Vec<i32>, line_nr: usize) {
-    if stack.len() == 1 {
-        println!("Line {}: {}", line_nr + 1, stack.pop().unwrap());
+fn open_input(path: &str) -> io::Result<Box<dyn BufRead>> {
+    if path == "-" {
+        Ok(Box::new(BufReader::new(io::stdin())))
     } else {
-        println!("Line {}: invalid stack, exiting", line_nr + 1);
+        Ok(Box::new(BufReader::new(File::open(path)?)))
     }
-    stack.clear();
 }
 
-fn perform_op(stack: &mut Vec<i32>, op: &str) {
-    let val_1 = stack.pop().unwrap();
-    let val_2 = stack.pop().unwrap();
-    match op {
-        "+" => stack.push(val_2 + val_1),
-        "-" => stack.push(val_2 - val_1),
-        "*" => stack.push(val_2 * val_1),
-        "/" => {
-            if val_1 == 0 {
-                stack.clear();
-            } else {
-                stack.push(val_2 / val_1)
-            }
-        }
-        _ => println!("Received invalid operator: {}", op),
+fn setup<N: Number>(config: &Config) -> Result<Calculator<N>, String> {
+    let mut calculator = Calculator::with_stack_limit(config.stack_limit);
+    if let Some(path) = &config.prelude {
+        let source = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        calculator
+            .eval_source(&source)
+            .map_err(|diagnostic| format!("{}, {}", path, diagnostic))?;
+    }
+    Ok(calculator)
+}
+
+/// Evaluates `reader` line by line, returns whether every line succeeded.
+fn print_results<N: Number>(
+    reader: impl BufRead,
+    input: &str,
+    calculator: &mut Calculator<N>,
+    config: &Config,
+    prefix: &str,
+    out: &mut impl Write,
+    diagnostics: &mut impl Write,
+) -> io::Result<bool> {
+    let mut ok = true;
+    for (e, line) in reader.lines().enumerate() {
+        let line = line?;
+        let outcome = calculator
+            .evaluate(&line)
+            .map(|result| result.map(|value| value.to_string()));
+        ok &= outcome.is_ok();
+
+        let infix = match outcome {
+            Ok(Some(_)) if config.explain => to_infix(&line).ok(),
+            _ => None,
+        };
+        let record = Record {
+            input,
+            line: e + 1,
+            source: &line,
+            outcome,
+            depth: calculator.last_depth(),
+            infix,
+        };
+        output::write_record(config.format, &record, prefix, out, diagnostics)?;
     }
+    Ok(ok)
 }
 
-fn contains_two_operands(line: &str) -> bool {
-    let mut count = 0u8;
-    for expr in line.split_whitespace() {
-        if expr.parse::<i32>().is_ok() {
-            count += 1;
+/// Evaluates a single line of whitespace separated RPN tokens, e.g. `2 3 +`.
+///
+/// The line must leave exactly one value on the stack, which is returned.
+pub fn evaluate(line: &str) -> Result<Value, RpnError> {
+    evaluate_as::<Value>(line)
+}
+
+/// Evaluates a line using arbitrary precision rationals.
+pub fn evaluate_exact(line: &str) -> Result<BigRational, RpnError> {
+    evaluate_as::<BigRational>(line)
+}
+
+/// Evaluates a line with any `Number` implementation.
+pub fn evaluate_as<N: Number>(line: &str) -> Result<N, RpnError> {
+    let mut calculator = Calculator::new();
+    calculator.eval_line(line).map_err(|d| d.error)?;
+    calculator.take_result()
+}
+
+/// Interactive mode: the stack persists between lines and is shown after
+/// each input. Besides RPN tokens a line can be one of the commands
+/// `.s` (show stack), `undo`, `reset` or `quit`.
+pub fn repl(config: &Config) {
+    match config.mode {
+        Mode::Checked => repl_with::<Value>(config),
+        Mode::Exact => repl_with::<BigRational>(config),
+    }
+}
+
+fn repl_with<N: Number>(config: &Config) {
+    let mut calculator = setup::<N>(config).unwrap_or_else(|message| {
+        println!("{}", message);
+        Calculator::with_stack_limit(config.stack_limit)
+    });
+    calculator.set_undo_limit(UNDO_LIMIT);
+    println!("RPN calculator, commands: .s undo reset quit");
+    let stdin = io::stdin();
+    let mut line_nr = 0;
+
+    loop {
+        line_nr += 1;
+        print!("> ");
+        io::stdout().flush().expect("Could not flush stdout");
+
+        let mut input = String::new();
+        match stdin.lock().read_line(&mut input) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => (),
         }
-        if count == 2 {
-            return true;
+
+        match input.trim() {
+            "" | ".s" => (),
+            "quit" => break,
+            "undo" => {
+                if !calculator.undo() {
+                    println!("Nothing to undo");
+                }
+            }
+            "reset" => calculator.reset(),
+            _ => {
+                if let Err(diagnostic) = calculator.eval_line(&input) {
+                    println!("Error on {}", diagnostic.on_line(line_nr));
+                }
+            }
         }
+        print_stack(calculator.stack());
+    }
+}
+
+fn print_stack<N: Number>(stack: &[N]) {
+    let values: Vec<String> = stack.iter().map(|value| value.to_string()).collect();
+    println!("<{}> {}", stack.len(), values.join(" "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: &str) -> RpnError {
+        evaluate(line).unwrap_err()
+    }
+
+    #[test]
+    fn operators_need_enough_operands() {
+        assert_eq!(
+            error("1 +"),
+            RpnError::StackUnderflow {
+                op: "+".to_string(),
+                needed: 2,
+                found: 1,
+            }
+        );
+        assert_eq!(
+            error("neg"),
+            RpnError::StackUnderflow {
+                op: "neg".to_string(),
+                needed: 1,
+                found: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn a_line_must_leave_one_value() {
+        assert_eq!(error("1 2"), RpnError::LeftoverOperands(2));
+        assert_eq!(error("1 2 3 +"), RpnError::LeftoverOperands(2));
+        assert_eq!(error(""), RpnError::EmptyExpression);
+    }
+
+    #[test]
+    fn refuses_to_divide_by_zero() {
+        assert_eq!(error("1 0 /"), RpnError::DivisionByZero);
+        assert_eq!(error("1.5 0 /"), RpnError::DivisionByZero);
+        assert_eq!(error("1 0 %"), RpnError::DivisionByZero);
+        assert_eq!(evaluate_exact("1 3 / 0 /"), Err(RpnError::DivisionByZero));
+    }
+
+    #[test]
+    fn names_unknown_tokens() {
+        assert_eq!(
+            error("1 2 plus"),
+            RpnError::UnknownToken("plus".to_string())
+        );
+        assert_eq!(error("1 2 ++"), RpnError::UnknownToken("++".to_string()));
+    }
+
+    #[test]
+    fn reports_results_too_large_to_hold() {
+        assert_eq!(error("1e308 10 *"), RpnError::Overflow("*".to_string()));
+        assert_eq!(
+            evaluate_as::<i64>("9223372036854775807 1 +"),
+            Err(RpnError::Overflow("+".to_string()))
+        );
+    }
+
+    #[test]
+    fn the_default_limit_fits_the_sample() {
+        let sample = std::fs::read_to_string(DEFAULT_INPUT).unwrap();
+        let line = sample.lines().nth(10).unwrap();
+        let mut calculator = Calculator::<Value>::with_stack_limit(Config::default().stack_limit);
+        assert_eq!(calculator.evaluate(line), Ok(Some(Value::Int(38))));
+    }
+
+    #[test]
+    fn diagnostics_name_the_line_and_column() {
+        let mut calculator = Calculator::<Value>::new();
+        let diagnostic = calculator.eval_source("1 2 + drop\n1 +").unwrap_err();
+        assert_eq!(
+            diagnostic.to_string(),
+            "line 2, col 3: `+` needs 2 operands, stack has 1"
+        );
+        // Errors found at the end of the input have no column
+        let diagnostic = calculator.eval_source("1\n: sq dup *").unwrap_err();
+        assert!(diagnostic.to_string().starts_with("line 2: "));
     }
-    false
 }
@@ -0,0 +1,395 @@
+use super::environment::{check_name, Environment, MAX_WORD_DEPTH};
+use super::errors::{Diagnostic, RpnError};
+use super::number::Number;
+use super::ops::Op;
+use super::tokenizer::tokenize;
+use super::DEFAULT_STACK_LIMIT;
+use std::collections::VecDeque;
+
+/// An RPN stack that survives across lines, together with the user defined
+/// words and variables.
+///
+/// Each line is applied atomically: if any token fails, the calculator is
+/// left as it was before the line. With an undo limit set, the whole state
+/// before each of the last successful lines is kept so they can be undone.
+#[derive(Debug, Clone)]
+pub struct Calculator<N: Number> {
+    state: State<N>,
+    /// Oldest first, at most `undo_limit` long.
+    history: VecDeque<State<N>>,
+    /// Lines that can be undone, none by default so batch runs keep no
+    /// copies of the state.
+    undo_limit: usize,
+    stack_limit: usize,
+    /// Stack depth at the end of the last line, or before the token that
+    /// failed.
+    last_depth: usize,
+    /// Whether the last token run defined a word or stored a variable,
+    /// rather than left something to show.
+    quiet: bool,
+    /// Words and variables the current line overwrote, oldest first, so a
+    /// failing line can put them back.
+    journal: Vec<Change<N>>,
+}
+
+#[derive(Debug, Clone)]
+struct State<N: Number> {
+    stack: Vec<N>,
+    env: Environment<N>,
+    /// A `: name ...` definition that has not seen its `;` yet, definitions
+    /// may span several lines.
+    definition: Option<Definition>,
+}
+
+/// An environment entry as it was before the current line changed it.
+#[derive(Debug, Clone)]
+enum Change<N: Number> {
+    Word(String, Option<Vec<String>>),
+    Variable(String, Option<N>),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Definition {
+    name: Option<String>,
+    body: Vec<String>,
+}
+
+impl<N: Number> Default for Calculator<N> {
+    fn default() -> Self {
+        Calculator::new()
+    }
+}
+
+impl<N: Number> Calculator<N> {
+    pub fn new() -> Calculator<N> {
+        Calculator::with_stack_limit(DEFAULT_STACK_LIMIT)
+    }
+
+    /// A calculator whose stack may hold at most `stack_limit` values.
+    pub fn with_stack_limit(stack_limit: usize) -> Calculator<N> {
+        Calculator {
+            state: State {
+                stack: Vec::new(),
+                env: Environment::new(),
+                definition: None,
+            },
+            history: VecDeque::new(),
+            undo_limit: 0,
+            stack_limit,
+            last_depth: 0,
+            quiet: false,
+            journal: Vec::new(),
+        }
+    }
+
+    /// Keeps the state before each of the last `limit` successful lines for
+    /// `undo`, forgetting older ones.
+    pub fn set_undo_limit(&mut self, limit: usize) {
+        self.undo_limit = limit;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    /// Values currently on the stack, bottom first.
+    pub fn stack(&self) -> &[N] {
+        &self.state.stack
+    }
+
+    pub fn env(&self) -> &Environment<N> {
+        &self.state.env
+    }
+
+    /// How many values the last line left on the stack, or when it failed,
+    /// how many the stack held just before the failing token.
+    pub fn last_depth(&self) -> usize {
+        self.last_depth
+    }
+
+    /// Runs every token of `line` against the current stack.
+    pub fn eval_line(&mut self, line: &str) -> Result<(), Diagnostic> {
+        self.quiet = false;
+        // Only undo needs a copy of the whole state. Rolling back a failed
+        // line takes the stack and definition, the journal has the rest
+        let snapshot = if self.undo_limit > 0 {
+            Some(self.state.clone())
+        } else {
+            None
+        };
+        let stack = self.state.stack.clone();
+        let definition = self.state.definition.clone();
+        self.journal.clear();
+        let result = tokenize(line).into_iter().try_for_each(|(column, token)| {
+            self.eval_token(token, 0)
+                .map_err(|err| Diagnostic::new(err, Some(column)))
+        });
+        match result {
+            Ok(()) => {
+                self.last_depth = self.state.stack.len();
+                if let Some(snapshot) = snapshot {
+                    if self.history.len() == self.undo_limit {
+                        self.history.pop_front();
+                    }
+                    self.history.push_back(snapshot);
+                }
+                Ok(())
+            }
+            Err(err) => {
+                self.state.stack = stack;
+                self.state.definition = definition;
+                while let Some(change) = self.journal.pop() {
+                    match change {
+                        Change::Word(name, body) => self.state.env.restore_word(name, body),
+                        Change::Variable(name, value) => {
+                            self.state.env.restore_variable(name, value)
+                        }
+                    }
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Runs every line of `source`, e.g. a prelude of definitions, keeping
+    /// whatever it leaves on the stack.
+    pub fn eval_source(&mut self, source: &str) -> Result<(), Diagnostic> {
+        let mut line_count = 0;
+        for (e, line) in source.lines().enumerate() {
+            self.eval_line(line).map_err(|d| d.on_line(e + 1))?;
+            line_count = e + 1;
+        }
+        self.check_definitions_closed()
+            .map_err(|err| Diagnostic::new(err, None).on_line(line_count))
+    }
+
+    /// Evaluates `line` on an empty stack, using the words and variables of
+    /// the session. Returns `None` for a line that ends by defining a word
+    /// or storing a variable and leaves nothing behind, any other line has
+    /// to leave a single value.
+    pub fn evaluate(&mut self, line: &str) -> Result<Option<N>, Diagnostic> {
+        self.state.stack.clear();
+        self.eval_line(line)?;
+        if self.state.stack.is_empty() && self.quiet {
+            return Ok(None);
+        }
+        self.take_result()
+            .map(Some)
+            .map_err(|err| Diagnostic::new(err, None))
+    }
+
+    /// Restores the calculator to what it was before the last successful
+    /// line. Returns `false` when there is nothing left to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(previous) => {
+                self.state = previous;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Empties the stack and forgets the undo history, definitions and
+    /// variables are kept.
+    pub fn reset(&mut self) {
+        self.state.stack.clear();
+        self.state.definition = None;
+        self.history.clear();
+    }
+
+    /// Takes the single value a complete expression should leave behind.
+    pub fn take_result(&mut self) -> Result<N, RpnError> {
+        self.check_definitions_closed()?;
+        let stack = &mut self.state.stack;
+        match stack.len() {
+            0 => Err(RpnError::EmptyExpression),
+            1 => Ok(stack.pop().unwrap()),
+            n => Err(RpnError::LeftoverOperands(n)),
+        }
+    }
+
+    fn check_definitions_closed(&self) -> Result<(), RpnError> {
+        match &self.state.definition {
+            Some(definition) => Err(RpnError::UnterminatedDefinition(
+                definition.name.clone().unwrap_or_default(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn eval_token(&mut self, token: &str, depth: usize) -> Result<(), RpnError> {
+        if self.state.definition.is_some() {
+            self.quiet = true;
+            return self.compile_token(token);
+        }
+        self.quiet = token == ":" || token.starts_with('=');
+
+        // An operator pops its operands before it can fail
+        self.last_depth = self.state.stack.len();
+        if token == ":" {
+            self.state.definition = Some(Definition::default());
+        } else if token == ";" {
+            return Err(RpnError::MisplacedToken(token.to_string()));
+        } else if let Some(op) = Op::parse(token) {
+            self.perform_op(op)?;
+        } else if let Some(value) = N::parse(token) {
+            self.state.stack.push(value);
+        } else if let Some(name) = token.strip_prefix('=').filter(|name| !name.is_empty()) {
+            let value = self.state.stack.pop().ok_or(RpnError::StackUnderflow {
+                op: token.to_string(),
+                needed: 1,
+                found: 0,
+            })?;
+            let previous = self.state.env.store(name, value)?;
+            self.journal
+                .push(Change::Variable(name.to_string(), previous));
+        } else if let Some(body) = self.state.env.word(token) {
+            if depth >= MAX_WORD_DEPTH {
+                return Err(RpnError::RecursionLimit(token.to_string()));
+            }
+            let body = body.to_vec();
+            for inner in &body {
+                self.eval_token(inner, depth + 1)?;
+            }
+        } else if let Some(value) = self.state.env.load(token) {
+            self.state.stack.push(value.clone());
+        } else {
+            return Err(RpnError::UnknownToken(token.to_string()));
+        }
+
+        if self.state.stack.len() > self.stack_limit {
+            return Err(RpnError::StackLimit(self.stack_limit));
+        }
+        Ok(())
+    }
+
+    /// Collects the tokens of an open `: name ... ;` definition.
+    fn compile_token(&mut self, token: &str) -> Result<(), RpnError> {
+        let definition = self.state.definition.as_mut().unwrap();
+        match (token, &definition.name) {
+            (":", _) => Err(RpnError::MisplacedToken(token.to_string())),
+            (";", None) => Err(RpnError::InvalidName(token.to_string())),
+            (";", Some(name)) => {
+                let name = name.clone();
+                let body = std::mem::take(&mut definition.body);
+                self.state.definition = None;
+                let previous = self.state.env.define(&name, body)?;
+                self.journal.push(Change::Word(name, previous));
+                Ok(())
+            }
+            (_, None) => {
+                check_name::<N>(token)?;
+                definition.name = Some(token.to_string());
+                Ok(())
+            }
+            (_, Some(_)) => {
+                definition.body.push(token.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn perform_op(&mut self, op: Op) -> Result<(), RpnError> {
+        let stack = &mut self.state.stack;
+        if stack.len() < op.arity() {
+            return Err(RpnError::StackUnderflow {
+                op: op.to_string(),
+                needed: op.arity(),
+                found: stack.len(),
+            });
+        }
+
+        match op {
+            Op::Binary(op) => {
+                let rhs = stack.pop().unwrap();
+                let lhs = stack.pop().unwrap();
+                stack.push(N::binary(op, lhs, rhs)?);
+            }
+            Op::Unary(op) => {
+                let value = stack.pop().unwrap();
+                stack.push(N::unary(op, value)?);
+            }
+            Op::Stack(op) => op.apply(stack),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_no_history_unless_asked() {
+        let mut calculator = Calculator::<i64>::new();
+        calculator.eval_line("1 2").unwrap();
+        assert!(!calculator.undo());
+        assert_eq!(calculator.stack(), [1, 2]);
+    }
+
+    #[test]
+    fn undoes_at_most_the_limit() {
+        let mut calculator = Calculator::<i64>::new();
+        calculator.set_undo_limit(2);
+        for line in ["1", "2", "3"] {
+            calculator.eval_line(line).unwrap();
+        }
+        assert!(calculator.undo());
+        assert!(calculator.undo());
+        assert!(!calculator.undo());
+        assert_eq!(calculator.stack(), [1]);
+    }
+
+    #[test]
+    fn reports_the_depth_before_the_failing_token() {
+        let mut calculator = Calculator::<i64>::new();
+        assert!(calculator.eval_line("5 1 0 /").is_err());
+        assert_eq!(calculator.last_depth(), 3);
+        assert!(calculator.eval_line(": half 0 / ; 7 half").is_err());
+        assert_eq!(calculator.last_depth(), 2);
+        calculator.eval_line("1 2 +").unwrap();
+        assert_eq!(calculator.last_depth(), 1);
+    }
+
+    #[test]
+    fn lines_that_leave_nothing_fail_unless_they_define_or_store() {
+        let mut calculator = Calculator::<i64>::new();
+        for line in ["5 drop", "1 2 clear", "", ": nop ; nop"] {
+            let error = calculator.evaluate(line).unwrap_err();
+            assert_eq!(error.error, RpnError::EmptyExpression, "{:?}", line);
+        }
+        assert_eq!(calculator.evaluate(": sq dup * ;"), Ok(None));
+        assert_eq!(calculator.evaluate("3 =x"), Ok(None));
+        assert_eq!(calculator.evaluate(": setx =x ; 4 setx"), Ok(None));
+        assert_eq!(calculator.evaluate("x sq"), Ok(Some(16)));
+    }
+
+    #[test]
+    fn prelude_words_outlive_failed_lines() {
+        let mut calculator = Calculator::<i64>::new();
+        calculator.eval_source(": sq dup * ;\n2 =two").unwrap();
+        assert!(calculator.evaluate("1 0 /").is_err());
+        assert!(calculator.evaluate(": bad 1 0 / ; bad").is_err());
+        assert_eq!(calculator.evaluate("3 sq"), Ok(Some(9)));
+        assert_eq!(calculator.evaluate("two sq"), Ok(Some(4)));
+        assert!(calculator.env().word("bad").is_none());
+    }
+
+    #[test]
+    fn failed_lines_take_back_what_they_wrote() {
+        for undo_limit in [0, 10] {
+            let mut calculator = Calculator::<i64>::new();
+            calculator.set_undo_limit(undo_limit);
+            calculator.eval_line("7 : sq dup * ; 2 =x").unwrap();
+            let line = ": sq 1 + ; : cube dup sq * ; 5 =x 6 =y 9 : open 1 :";
+            assert!(calculator.eval_line(line).is_err());
+            assert_eq!(calculator.stack(), [7]);
+            assert_eq!(calculator.env().word("sq").unwrap(), ["dup", "*"]);
+            assert!(calculator.env().word("cube").is_none());
+            assert_eq!(calculator.env().load("x"), Some(&2));
+            assert!(calculator.env().load("y").is_none());
+            // The failed line left no definition open
+            assert_eq!(calculator.evaluate("3 sq"), Ok(Some(9)));
+        }
+    }
+}
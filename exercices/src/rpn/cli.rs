@@ -0,0 +1,116 @@
+use super::{repl, run, to_infix, to_rpn, Config, Format, Mode};
+
+const USAGE: &str = "\
+usage: exercices rpn [OPTIONS] [FILE]...
+
+Evaluates each line of the given files, `-` or no FILE reads stdin.
+Exits with status 1 if any line fails.
+
+options:
+    --exact             use arbitrary precision rationals
+    --prelude FILE      load definitions from FILE first
+    --stack-limit N     allow at most N values on the stack (default 1024)
+    --explain           show each line as infix next to its result
+    --format FORMAT     text (default), json (JSON Lines) or csv
+    --repl              start the interactive calculator
+    --to-rpn EXPR       print the RPN form of an infix expression
+    --to-infix LINE     print an RPN line as infix";
+
+/// What the command line asked for, besides the shared settings.
+enum Action {
+    Evaluate,
+    Repl,
+    ToRpn(String),
+    ToInfix(String),
+    Help,
+}
+
+/// Entry point of `exercices rpn`, returns the process exit status.
+pub fn main(args: &[String]) -> i32 {
+    match parse_args(args) {
+        Ok((config, action)) => match action {
+            Action::Evaluate => {
+                if run(&config) {
+                    0
+                } else {
+                    1
+                }
+            }
+            Action::Repl => {
+                repl(&config);
+                0
+            }
+            Action::ToRpn(expr) => match to_rpn(&expr) {
+                Ok(tokens) => {
+                    println!("{}", tokens.join(" "));
+                    0
+                }
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic);
+                    1
+                }
+            },
+            Action::ToInfix(line) => match to_infix(&line) {
+                Ok(infix) => {
+                    println!("{}", infix);
+                    0
+                }
+                Err(diagnostic) => {
+                    eprintln!("{}", diagnostic);
+                    1
+                }
+            },
+            Action::Help => {
+                println!("{}", USAGE);
+                0
+            }
+        },
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            2
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(Config, Action), String> {
+    let mut config = Config {
+        inputs: Vec::new(),
+        ..Config::default()
+    };
+    let mut action = Action::Evaluate;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match arg.as_str() {
+            "--exact" => config.mode = Mode::Exact,
+            "--explain" => config.explain = true,
+            "--repl" => action = Action::Repl,
+            "--format" => {
+                let name = value(arg)?;
+                config.format =
+                    Format::parse(&name).ok_or_else(|| format!("unknown format {}", name))?;
+            }
+            "--prelude" => config.prelude = Some(value(arg)?),
+            "--stack-limit" => {
+                config.stack_limit = value(arg)?
+                    .parse()
+                    .map_err(|_| "--stack-limit must be a number".to_string())?
+            }
+            "--to-rpn" => action = Action::ToRpn(value(arg)?),
+            "--to-infix" => action = Action::ToInfix(value(arg)?),
+            "-h" | "--help" => action = Action::Help,
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            input => config.inputs.push(input.to_string()),
+        }
+    }
+
+    if config.inputs.is_empty() {
+        config.inputs.push("-".to_string());
+    }
+    Ok((config, action))
+}
@@ -0,0 +1,338 @@
+use super::environment::{Environment, MAX_WORD_DEPTH};
+use super::errors::{Diagnostic, RpnError};
+use super::number::Number;
+use super::ops::{BinaryOp, Op, UnaryOp};
+use super::tokenizer::tokenize;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Expression tree of an RPN line. Subtrees made only of literals are folded
+/// into a single literal while the tree is built.
+///
+/// Subtrees are shared rather than copied, so `dup` costs the same however
+/// large the value it copies.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<N: Number> {
+    Literal(N),
+    Var(String),
+    Binary(BinaryOp, Rc<Expr<N>>, Rc<Expr<N>>),
+    Unary(UnaryOp, Rc<Expr<N>>),
+}
+
+impl<N: Number> Expr<N> {
+    /// Builds the tree for `line`, inlining the words defined in `env`.
+    /// Any other name is a variable that is bound when the program runs.
+    pub fn parse(line: &str, env: &Environment<N>) -> Result<Expr<N>, Diagnostic> {
+        let mut stack: Vec<Rc<Expr<N>>> = Vec::new();
+        for (column, token) in tokenize(line) {
+            push_token(&mut stack, token, env, 0)
+                .map_err(|err| Diagnostic::new(err, Some(column)))?;
+        }
+
+        match stack.len() {
+            0 => Err(Diagnostic::new(RpnError::EmptyExpression, None)),
+            1 => Ok(Rc::unwrap_or_clone(stack.pop().unwrap())),
+            n => Err(Diagnostic::new(RpnError::LeftoverOperands(n), None)),
+        }
+    }
+
+    fn binary(op: BinaryOp, lhs: Rc<Expr<N>>, rhs: Rc<Expr<N>>) -> Rc<Expr<N>> {
+        if let (Expr::Literal(a), Expr::Literal(b)) = (&*lhs, &*rhs) {
+            // Errors such as division by zero are left for run time
+            if let Ok(value) = N::binary(op, a.clone(), b.clone()) {
+                return Rc::new(Expr::Literal(value));
+            }
+        }
+        Rc::new(Expr::Binary(op, lhs, rhs))
+    }
+
+    fn unary(op: UnaryOp, operand: Rc<Expr<N>>) -> Rc<Expr<N>> {
+        if let Expr::Literal(a) = &*operand {
+            if let Ok(value) = N::unary(op, a.clone()) {
+                return Rc::new(Expr::Literal(value));
+            }
+        }
+        Rc::new(Expr::Unary(op, operand))
+    }
+
+    /// The operands of the outermost operator.
+    fn children(&self) -> Vec<&Rc<Expr<N>>> {
+        match self {
+            Expr::Literal(_) | Expr::Var(_) => Vec::new(),
+            Expr::Binary(_, lhs, rhs) => vec![lhs, rhs],
+            Expr::Unary(_, operand) => vec![operand],
+        }
+    }
+}
+
+fn push_token<N: Number>(
+    stack: &mut Vec<Rc<Expr<N>>>,
+    token: &str,
+    env: &Environment<N>,
+    depth: usize,
+) -> Result<(), RpnError> {
+    if let Some(op) = Op::parse(token) {
+        if stack.len() < op.arity() {
+            return Err(RpnError::StackUnderflow {
+                op: op.to_string(),
+                needed: op.arity(),
+                found: stack.len(),
+            });
+        }
+        match op {
+            Op::Binary(op) => {
+                let rhs = stack.pop().unwrap();
+                let lhs = stack.pop().unwrap();
+                stack.push(Expr::binary(op, lhs, rhs));
+            }
+            Op::Unary(op) => {
+                let operand = stack.pop().unwrap();
+                stack.push(Expr::unary(op, operand));
+            }
+            Op::Stack(op) => op.apply(stack),
+        }
+    } else if let Some(value) = N::parse(token) {
+        stack.push(Rc::new(Expr::Literal(value)));
+    } else if let Some(body) = env.word(token) {
+        if depth >= MAX_WORD_DEPTH {
+            return Err(RpnError::RecursionLimit(token.to_string()));
+        }
+        for inner in body {
+            push_token(stack, inner, env, depth + 1)?;
+        }
+    } else if token.contains([':', ';', '=']) {
+        // Definitions and assignments only make sense in a session
+        return Err(RpnError::MisplacedToken(token.to_string()));
+    } else {
+        stack.push(Rc::new(Expr::Var(token.to_string())));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Instr<N: Number> {
+    Push(N),
+    /// Push the value bound to the variable in this slot.
+    Load(usize),
+    Binary(BinaryOp),
+    Unary(UnaryOp),
+    /// Copy the top of the stack into this temporary, for shared subtrees.
+    Save(usize),
+    /// Push the value saved in this temporary.
+    Restore(usize),
+}
+
+/// A line compiled once into flat stack code, to be run many times with
+/// different variable values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program<N: Number> {
+    code: Vec<Instr<N>>,
+    variables: Vec<String>,
+    /// Number of temporaries holding shared subtrees.
+    temporaries: usize,
+    max_depth: usize,
+}
+
+/// Tracks shared subtrees while a program is emitted.
+struct Emitter<N: Number> {
+    program: Program<N>,
+    /// How many operators use each subtree, by address.
+    uses: HashMap<*const Expr<N>, usize>,
+    /// Temporaries of subtrees already computed.
+    saved: HashMap<*const Expr<N>, usize>,
+}
+
+/// Compiles `line` into a `Program`, see `Expr::parse`.
+pub fn compile<N: Number>(line: &str, env: &Environment<N>) -> Result<Program<N>, Diagnostic> {
+    Ok(Program::from_expr(&Expr::parse(line, env)?))
+}
+
+impl<N: Number> Program<N> {
+    /// Each subtree shared by several operators is computed once and kept
+    /// in a temporary.
+    pub fn from_expr(expr: &Expr<N>) -> Program<N> {
+        let mut emitter = Emitter {
+            program: Program {
+                code: Vec::new(),
+                variables: Vec::new(),
+                temporaries: 0,
+                max_depth: 0,
+            },
+            uses: HashMap::new(),
+            saved: HashMap::new(),
+        };
+        for child in expr.children() {
+            emitter.count_uses(child);
+        }
+        emitter.emit(expr, 0);
+        emitter.program
+    }
+
+    /// Names of the free variables, in the order `run` expects their values.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Runs the program with `values[i]` bound to `variables()[i]`.
+    pub fn run(&self, values: &[N]) -> Result<N, RpnError> {
+        if let Some(missing) = self.variables.get(values.len()) {
+            return Err(RpnError::UnboundVariable(missing.clone()));
+        }
+
+        let mut stack: Vec<N> = Vec::with_capacity(self.max_depth);
+        let mut temporaries: Vec<Option<N>> = vec![None; self.temporaries];
+        for instr in &self.code {
+            // The tree guarantees every operator finds its operands
+            match instr {
+                Instr::Push(value) => stack.push(value.clone()),
+                Instr::Load(slot) => stack.push(values[*slot].clone()),
+                Instr::Binary(op) => {
+                    let rhs = stack.pop().unwrap();
+                    let lhs = stack.pop().unwrap();
+                    stack.push(N::binary(*op, lhs, rhs)?);
+                }
+                Instr::Unary(op) => {
+                    let operand = stack.pop().unwrap();
+                    stack.push(N::unary(*op, operand)?);
+                }
+                Instr::Save(slot) => temporaries[*slot] = stack.last().cloned(),
+                Instr::Restore(slot) => {
+                    stack.push(temporaries[*slot].clone().expect("saved before use"))
+                }
+            }
+        }
+        // Reported like the interpreter does, should the code ever leave more
+        match stack.len() {
+            1 => Ok(stack.pop().unwrap()),
+            n => Err(RpnError::LeftoverOperands(n)),
+        }
+    }
+
+    /// Runs the program with the variables stored in a session environment.
+    pub fn run_env(&self, env: &Environment<N>) -> Result<N, RpnError> {
+        let values = self
+            .variables
+            .iter()
+            .map(|name| {
+                env.load(name)
+                    .cloned()
+                    .ok_or_else(|| RpnError::UnboundVariable(name.clone()))
+            })
+            .collect::<Result<Vec<N>, RpnError>>()?;
+        self.run(&values)
+    }
+}
+
+impl<N: Number> Emitter<N> {
+    /// Counts the operators using `expr`, each shared subtree walked once.
+    fn count_uses(&mut self, expr: &Rc<Expr<N>>) {
+        let uses = self.uses.entry(Rc::as_ptr(expr)).or_insert(0);
+        *uses += 1;
+        if *uses == 1 {
+            for child in expr.children() {
+                self.count_uses(child);
+            }
+        }
+    }
+
+    /// Emits the code for an operand, reusing its temporary if it was
+    /// computed before.
+    fn emit_shared(&mut self, expr: &Rc<Expr<N>>, depth: usize) {
+        let key = Rc::as_ptr(expr);
+        if let Some(&slot) = self.saved.get(&key) {
+            self.program.code.push(Instr::Restore(slot));
+            return;
+        }
+        self.emit(expr, depth);
+        // Leaves are as cheap to push again as to restore
+        if self.uses[&key] > 1 && !expr.children().is_empty() {
+            let slot = self.program.temporaries;
+            self.program.temporaries += 1;
+            self.program.code.push(Instr::Save(slot));
+            self.saved.insert(key, slot);
+        }
+    }
+
+    /// Emits the code for `expr` when `depth` values are already on the stack.
+    fn emit(&mut self, expr: &Expr<N>, depth: usize) {
+        let program = &mut self.program;
+        program.max_depth = program.max_depth.max(depth + 1);
+        match expr {
+            Expr::Literal(value) => program.code.push(Instr::Push(value.clone())),
+            Expr::Var(name) => {
+                let slot = match program.variables.iter().position(|var| var == name) {
+                    Some(slot) => slot,
+                    None => {
+                        program.variables.push(name.clone());
+                        program.variables.len() - 1
+                    }
+                };
+                program.code.push(Instr::Load(slot));
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                self.emit_shared(lhs, depth);
+                self.emit_shared(rhs, depth + 1);
+                self.program.code.push(Instr::Binary(*op));
+            }
+            Expr::Unary(op, operand) => {
+                self.emit_shared(operand, depth);
+                self.program.code.push(Instr::Unary(*op));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::evaluate_as;
+    use super::*;
+
+    /// Compiles `line` and runs it with `x` bound to 3.
+    fn run(line: &str) -> Result<i64, RpnError> {
+        let program = compile::<i64>(line, &Environment::new()).unwrap();
+        let values: Vec<i64> = program.variables().iter().map(|_| 3).collect();
+        program.run(&values)
+    }
+
+    #[test]
+    fn matches_the_interpreter_through_stack_operators() {
+        for line in [
+            "2 3 + dup *",
+            "1 2 swap -",
+            "1 2 over - *",
+            "1 2 3 rot - -",
+            "4 x dup * swap -",
+        ] {
+            let literal = line.replace('x', "3");
+            assert_eq!(run(line), evaluate_as::<i64>(&literal), "{}", line);
+        }
+    }
+
+    #[test]
+    fn shares_duplicated_subtrees() {
+        // x squared 22 times over, 2^22 leaves were the tree copied
+        let line = format!("x {}", "dup * ".repeat(22));
+        let program = compile::<i64>(&line, &Environment::new()).unwrap();
+        assert!(
+            program.code.len() < 100,
+            "{} instructions",
+            program.code.len()
+        );
+
+        let program = compile::<i64>("x 1 + dup dup * *", &Environment::new()).unwrap();
+        assert_eq!(program.run(&[2]), Ok(27));
+    }
+
+    #[test]
+    fn reports_values_left_on_the_stack() {
+        let program = Program::<i64> {
+            code: vec![Instr::Push(1), Instr::Push(2)],
+            variables: Vec::new(),
+            temporaries: 0,
+            max_depth: 2,
+        };
+        assert_eq!(program.run(&[]), Err(RpnError::LeftoverOperands(2)));
+        let error = compile::<i64>("1 2", &Environment::new()).unwrap_err();
+        assert_eq!(error.error, RpnError::LeftoverOperands(2));
+    }
+}
@@ -0,0 +1,92 @@
+use super::errors::RpnError;
+use super::number::Number;
+use super::ops::Op;
+use std::collections::HashMap;
+
+/// How deeply user defined words may call each other.
+pub const MAX_WORD_DEPTH: usize = 64;
+
+/// User defined words and variables, shared by every line of a session.
+#[derive(Debug, Clone)]
+pub struct Environment<N: Number> {
+    words: HashMap<String, Vec<String>>,
+    variables: HashMap<String, N>,
+}
+
+impl<N: Number> Default for Environment<N> {
+    fn default() -> Self {
+        Environment::new()
+    }
+}
+
+impl<N: Number> Environment<N> {
+    pub fn new() -> Environment<N> {
+        Environment {
+            words: HashMap::new(),
+            variables: HashMap::new(),
+        }
+    }
+
+    /// Defines (or redefines) a word as a sequence of tokens, returning the
+    /// body it replaces.
+    pub fn define(
+        &mut self,
+        name: &str,
+        body: Vec<String>,
+    ) -> Result<Option<Vec<String>>, RpnError> {
+        check_name::<N>(name)?;
+        Ok(self.words.insert(name.to_string(), body))
+    }
+
+    pub fn word(&self, name: &str) -> Option<&[String]> {
+        self.words.get(name).map(|body| body.as_slice())
+    }
+
+    /// Sets a variable, returning the value it replaces.
+    pub fn store(&mut self, name: &str, value: N) -> Result<Option<N>, RpnError> {
+        check_name::<N>(name)?;
+        Ok(self.variables.insert(name.to_string(), value))
+    }
+
+    pub fn load(&self, name: &str) -> Option<&N> {
+        self.variables.get(name)
+    }
+
+    /// Puts a word back as `define` found it, `None` when it did not exist.
+    pub(super) fn restore_word(&mut self, name: String, body: Option<Vec<String>>) {
+        match body {
+            Some(body) => self.words.insert(name, body),
+            None => self.words.remove(&name),
+        };
+    }
+
+    /// Puts a variable back as `store` found it.
+    pub(super) fn restore_variable(&mut self, name: String, value: Option<N>) {
+        match value {
+            Some(value) => self.variables.insert(name, value),
+            None => self.variables.remove(&name),
+        };
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = (&String, &Vec<String>)> {
+        self.words.iter()
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = (&String, &N)> {
+        self.variables.iter()
+    }
+}
+
+/// Names must not shadow numbers or builtins, nor contain the definition
+/// and assignment markers.
+pub fn check_name<N: Number>(name: &str) -> Result<(), RpnError> {
+    let reserved = name.is_empty()
+        || name.contains([':', ';', '='])
+        || Op::parse(name).is_some()
+        || N::parse(name).is_some();
+    if reserved {
+        Err(RpnError::InvalidName(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
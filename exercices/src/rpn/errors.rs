@@ -0,0 +1,162 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpnError {
+    /// An operator was reached with fewer operands on the stack than it needs.
+    StackUnderflow {
+        op: String,
+        needed: usize,
+        found: usize,
+    },
+    /// The line finished with more than one value left on the stack.
+    LeftoverOperands(usize),
+    /// The line contained no tokens at all.
+    EmptyExpression,
+    DivisionByZero,
+    /// A token that is neither a number nor a known operator.
+    UnknownToken(String),
+    /// The result of an operation does not fit in the number type.
+    Overflow(String),
+    /// The operands are outside of what the operator is defined for,
+    /// e.g. the square root of a negative number.
+    InvalidOperand {
+        op: String,
+        reason: &'static str,
+    },
+    /// Pushing another value would exceed the stack capacity.
+    StackLimit(usize),
+    /// A word or variable name that clashes with a number or a builtin.
+    InvalidName(String),
+    /// A `:` inside a definition or a `;` outside of one.
+    MisplacedToken(String),
+    /// The input ended before the definition of this word was closed.
+    UnterminatedDefinition(String),
+    /// Words called each other too deeply, usually through recursion.
+    RecursionLimit(String),
+    /// An infix expression with a `(` or `)` that has no partner.
+    UnbalancedParenthesis,
+    /// An infix expression that stops where an operand is expected.
+    UnexpectedEnd,
+    /// An infix call of a builtin with the wrong number of arguments.
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A compiled program was run without a value for this variable.
+    UnboundVariable(String),
+}
+
+impl RpnError {
+    pub fn invalid_operand(op: impl fmt::Display, reason: &'static str) -> RpnError {
+        RpnError::InvalidOperand {
+            op: op.to_string(),
+            reason,
+        }
+    }
+
+    /// Stable, machine readable name of the variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RpnError::StackUnderflow { .. } => "stack_underflow",
+            RpnError::LeftoverOperands(_) => "leftover_operands",
+            RpnError::EmptyExpression => "empty_expression",
+            RpnError::DivisionByZero => "division_by_zero",
+            RpnError::UnknownToken(_) => "unknown_token",
+            RpnError::Overflow(_) => "overflow",
+            RpnError::InvalidOperand { .. } => "invalid_operand",
+            RpnError::StackLimit(_) => "stack_limit",
+            RpnError::InvalidName(_) => "invalid_name",
+            RpnError::MisplacedToken(_) => "misplaced_token",
+            RpnError::UnterminatedDefinition(_) => "unterminated_definition",
+            RpnError::RecursionLimit(_) => "recursion_limit",
+            RpnError::UnbalancedParenthesis => "unbalanced_parenthesis",
+            RpnError::UnexpectedEnd => "unexpected_end",
+            RpnError::ArgumentCount { .. } => "argument_count",
+            RpnError::UnboundVariable(_) => "unbound_variable",
+        }
+    }
+}
+
+impl fmt::Display for RpnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RpnError::StackUnderflow { op, needed, found } => {
+                write!(f, "`{}` needs {} operands, stack has {}", op, needed, found)
+            }
+            RpnError::LeftoverOperands(n) => {
+                write!(f, "{} values left on the stack, expected 1", n)
+            }
+            RpnError::EmptyExpression => write!(f, "empty expression"),
+            RpnError::DivisionByZero => write!(f, "division by zero"),
+            RpnError::UnknownToken(token) => write!(f, "unknown token `{}`", token),
+            RpnError::Overflow(op) => write!(f, "`{}` overflowed", op),
+            RpnError::InvalidOperand { op, reason } => write!(f, "`{}`: {}", op, reason),
+            RpnError::StackLimit(limit) => {
+                write!(f, "stack capacity of {} values reached", limit)
+            }
+            RpnError::InvalidName(name) => write!(f, "`{}` cannot be used as a name", name),
+            RpnError::MisplacedToken(token) => write!(f, "unexpected `{}`", token),
+            RpnError::UnterminatedDefinition(name) => {
+                write!(f, "definition of `{}` is missing its `;`", name)
+            }
+            RpnError::RecursionLimit(word) => write!(f, "`{}` nests too deeply", word),
+            RpnError::UnbalancedParenthesis => write!(f, "unbalanced parenthesis"),
+            RpnError::UnexpectedEnd => write!(f, "expression ends where an operand is expected"),
+            RpnError::ArgumentCount {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` takes {} argument{}, got {}",
+                function,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found
+            ),
+            RpnError::UnboundVariable(name) => write!(f, "no value bound to `{}`", name),
+        }
+    }
+}
+
+impl Error for RpnError {}
+
+/// An `RpnError` together with where in the input it happened.
+///
+/// Lines and columns are 1-based, columns count characters. Errors about the
+/// line as a whole, such as leftover operands, have no column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: Option<usize>,
+    pub error: RpnError,
+}
+
+impl Diagnostic {
+    pub fn new(error: RpnError, column: Option<usize>) -> Diagnostic {
+        Diagnostic {
+            line: 1,
+            column,
+            error,
+        }
+    }
+
+    /// Places the diagnostic on line `line` of a multi-line input.
+    pub fn on_line(mut self, line: usize) -> Diagnostic {
+        self.line = line;
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "line {}, col {}: {}", self.line, column, self.error),
+            None => write!(f, "line {}: {}", self.line, self.error),
+        }
+    }
+}
+
+impl Error for Diagnostic {}
@@ -0,0 +1,446 @@
+use super::errors::{Diagnostic, RpnError};
+use super::number::Number;
+use super::ops::{BinaryOp, Op, UnaryOp};
+use super::tokenizer::tokenize;
+use super::value::Value;
+
+/// Binding power of prefix `-`, between the multiplicative operators and `^`
+/// so that `-2 ^ 2` is `-(2 ^ 2)`.
+const NEG_PRECEDENCE: u8 = 3;
+/// Binding power of anything that never needs parentheses: numbers,
+/// variables and function calls.
+const ATOM_PRECEDENCE: u8 = u8::MAX;
+
+fn precedence(op: BinaryOp) -> Option<u8> {
+    match op {
+        BinaryOp::Add | BinaryOp::Sub => Some(1),
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::IntDiv | BinaryOp::Rem => Some(2),
+        BinaryOp::Pow => Some(4),
+        // Written as functions, `min(a, b)`
+        BinaryOp::Min | BinaryOp::Max => None,
+    }
+}
+
+fn right_associative(op: BinaryOp) -> bool {
+    op == BinaryOp::Pow
+}
+
+/// Operators where `a op (b op c)` equals `(a op b) op c`.
+fn associative(op: BinaryOp) -> bool {
+    matches!(op, BinaryOp::Add | BinaryOp::Mul)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Number(&'a str),
+    Ident(&'a str),
+    Operator(&'a str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl<'a> Token<'a> {
+    fn text(self) -> &'a str {
+        match self {
+            Token::Number(text) | Token::Ident(text) | Token::Operator(text) => text,
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::Comma => ",",
+        }
+    }
+}
+
+/// Splits an infix expression into tokens with their 1-based columns.
+fn lex(expr: &str) -> Result<Vec<(usize, Token<'_>)>, Diagnostic> {
+    let chars: Vec<(usize, char)> = expr.char_indices().collect();
+    let byte_at = |i: usize| chars.get(i).map_or(expr.len(), |&(byte, _)| byte);
+    let char_at = |i: usize| chars.get(i).map(|&(_, c)| c);
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while let Some(c) = char_at(i) {
+        let start = i;
+        i += 1;
+        let token = match c {
+            _ if c.is_whitespace() => continue,
+            '0'..='9' | '.' => {
+                while char_at(i).is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    i += 1;
+                }
+                // Exponent, only when digits follow so `2e` stays an error
+                if let Some('e') | Some('E') = char_at(i) {
+                    let sign = matches!(char_at(i + 1), Some('+') | Some('-')) as usize;
+                    if char_at(i + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                        i += 1 + sign;
+                        while char_at(i).is_some_and(|c| c.is_ascii_digit()) {
+                            i += 1;
+                        }
+                    }
+                }
+                Token::Number(&expr[byte_at(start)..byte_at(i)])
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                while char_at(i).is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    i += 1;
+                }
+                Token::Ident(&expr[byte_at(start)..byte_at(i)])
+            }
+            '/' if char_at(i) == Some('/') => {
+                i += 1;
+                Token::Operator("//")
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => Token::Operator(&expr[byte_at(start)..byte_at(i)]),
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            _ => {
+                let error = RpnError::UnknownToken(c.to_string());
+                return Err(Diagnostic::new(error, Some(start + 1)));
+            }
+        };
+        tokens.push((start + 1, token));
+    }
+    Ok(tokens)
+}
+
+/// Operators waiting on the shunting-yard stack.
+#[derive(Debug, Clone, Copy)]
+enum Pending<'a> {
+    Binary(BinaryOp),
+    Neg,
+    LParen,
+    Function(&'a str),
+}
+
+impl<'a> Pending<'a> {
+    /// Whether this operator must be output before `incoming` is pushed.
+    fn binds_before(self, incoming: BinaryOp) -> bool {
+        let incoming_prec = precedence(incoming).unwrap();
+        match self {
+            Pending::Binary(op) => {
+                let prec = precedence(op).unwrap();
+                prec > incoming_prec || (prec == incoming_prec && !right_associative(incoming))
+            }
+            Pending::Neg => NEG_PRECEDENCE >= incoming_prec,
+            Pending::LParen | Pending::Function(_) => false,
+        }
+    }
+
+    fn output(self) -> String {
+        match self {
+            Pending::Binary(op) => op.to_string(),
+            Pending::Neg => UnaryOp::Neg.to_string(),
+            Pending::Function(name) => name.to_string(),
+            Pending::LParen => unreachable!("parentheses are never output"),
+        }
+    }
+}
+
+/// Converts an infix expression such as `(2 + 3) * 4` into the RPN tokens
+/// the evaluator reads, `2 3 + 4 *`.
+///
+/// Supports `+ - * / // % ^`, prefix `-`, parentheses, variables and
+/// function calls such as `sqrt(x)` or `max(a, b)`.
+pub fn to_rpn(expr: &str) -> Result<Vec<String>, Diagnostic> {
+    let tokens = lex(expr)?;
+    let mut output: Vec<String> = Vec::new();
+    let mut pending: Vec<(usize, Pending)> = Vec::new();
+    // Arguments seen so far inside each open parenthesis
+    let mut arguments: Vec<usize> = Vec::new();
+    // Whether the next token has to start an operand rather than follow one
+    let mut expect_operand = true;
+
+    let misplaced = |column: usize, token: Token| {
+        Diagnostic::new(
+            RpnError::MisplacedToken(token.text().to_string()),
+            Some(column),
+        )
+    };
+    let unbalanced = |column: usize| Diagnostic::new(RpnError::UnbalancedParenthesis, Some(column));
+
+    for (i, &(column, token)) in tokens.iter().enumerate() {
+        match (token, expect_operand) {
+            (Token::Number(text), true) => {
+                output.push(text.to_string());
+                expect_operand = false;
+            }
+            (Token::Ident(name), true) => {
+                if let Some((_, Token::LParen)) = tokens.get(i + 1) {
+                    pending.push((column, Pending::Function(name)));
+                } else {
+                    output.push(name.to_string());
+                    expect_operand = false;
+                }
+            }
+            (Token::Operator("-"), true) => pending.push((column, Pending::Neg)),
+            (Token::Operator("+"), true) => (),
+            (Token::Operator(symbol), false) => {
+                let op = match Op::parse(symbol) {
+                    Some(Op::Binary(op)) => op,
+                    _ => return Err(misplaced(column, token)),
+                };
+                while let Some(&(_, top)) = pending.last() {
+                    if !top.binds_before(op) {
+                        break;
+                    }
+                    output.push(top.output());
+                    pending.pop();
+                }
+                pending.push((column, Pending::Binary(op)));
+                expect_operand = true;
+            }
+            (Token::LParen, true) => {
+                pending.push((column, Pending::LParen));
+                arguments.push(1);
+            }
+            (Token::RParen, false) | (Token::Comma, false) => {
+                loop {
+                    match pending.last() {
+                        None => return Err(unbalanced(column)),
+                        Some((_, Pending::LParen)) => break,
+                        Some(&(_, top)) => {
+                            output.push(top.output());
+                            pending.pop();
+                        }
+                    }
+                }
+                let function = match pending.len().checked_sub(2).map(|i| pending[i]) {
+                    Some((_, Pending::Function(name))) => Some(name),
+                    _ => None,
+                };
+                if token == Token::Comma {
+                    // Commas only split the arguments of a call
+                    if function.is_none() {
+                        return Err(misplaced(column, token));
+                    }
+                    *arguments.last_mut().unwrap() += 1;
+                    expect_operand = true;
+                    continue;
+                }
+                pending.pop();
+                let found = arguments.pop().unwrap();
+                if let Some(name) = function {
+                    // Builtins take a fixed number, words may take any
+                    if let Some(op) = Op::parse(name) {
+                        if op.arity() != found {
+                            let error = RpnError::ArgumentCount {
+                                function: name.to_string(),
+                                expected: op.arity(),
+                                found,
+                            };
+                            return Err(Diagnostic::new(error, Some(column)));
+                        }
+                    }
+                    output.push(name.to_string());
+                    pending.pop();
+                }
+            }
+            _ => return Err(misplaced(column, token)),
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err(Diagnostic::new(RpnError::EmptyExpression, None));
+    }
+    if expect_operand {
+        let end = expr.chars().count() + 1;
+        return Err(Diagnostic::new(RpnError::UnexpectedEnd, Some(end)));
+    }
+    while let Some((column, top)) = pending.pop() {
+        match top {
+            Pending::LParen | Pending::Function(_) => return Err(unbalanced(column)),
+            _ => output.push(top.output()),
+        }
+    }
+    Ok(output)
+}
+
+/// Renders an RPN line as infix with as few parentheses as possible, e.g.
+/// `2 3 + 4 *` becomes `(2 + 3) * 4`.
+///
+/// Stack words are followed symbolically, names that are not builtins are
+/// shown as variables. Definitions and assignments cannot be rendered.
+pub fn to_infix(line: &str) -> Result<String, Diagnostic> {
+    let mut stack: Vec<Rendered> = Vec::new();
+
+    for (column, token) in tokenize(line) {
+        let at = |error: RpnError| Diagnostic::new(error, Some(column));
+
+        if let Some(op) = Op::parse(token) {
+            if stack.len() < op.arity() {
+                return Err(at(RpnError::StackUnderflow {
+                    op: op.to_string(),
+                    needed: op.arity(),
+                    found: stack.len(),
+                }));
+            }
+            match op {
+                Op::Binary(op) => {
+                    let rhs = stack.pop().unwrap();
+                    let lhs = stack.pop().unwrap();
+                    stack.push(render_binary(op, lhs, rhs));
+                }
+                Op::Unary(op) => {
+                    let operand = stack.pop().unwrap();
+                    stack.push(render_unary(op, operand));
+                }
+                Op::Stack(op) => op.apply(&mut stack),
+            }
+        } else if Value::parse(token).is_some() {
+            let prec = if token.starts_with('-') {
+                NEG_PRECEDENCE
+            } else {
+                ATOM_PRECEDENCE
+            };
+            stack.push(Rendered::new(token.to_string(), prec));
+        } else if token.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            stack.push(Rendered::new(token.to_string(), ATOM_PRECEDENCE));
+        } else {
+            return Err(at(RpnError::UnknownToken(token.to_string())));
+        }
+    }
+
+    match stack.len() {
+        0 => Err(Diagnostic::new(RpnError::EmptyExpression, None)),
+        1 => Ok(stack.pop().unwrap().text),
+        n => Err(Diagnostic::new(RpnError::LeftoverOperands(n), None)),
+    }
+}
+
+/// Part of an expression rendered as infix.
+#[derive(Debug, Clone)]
+struct Rendered {
+    text: String,
+    /// Binding power of the outermost operator.
+    prec: u8,
+    /// The outermost binary operator written between its operands.
+    op: Option<BinaryOp>,
+}
+
+impl Rendered {
+    fn new(text: String, prec: u8) -> Rendered {
+        Rendered {
+            text,
+            prec,
+            op: None,
+        }
+    }
+}
+
+fn parenthesize(rendered: Rendered, needed: bool) -> String {
+    if needed {
+        format!("({})", rendered.text)
+    } else {
+        rendered.text
+    }
+}
+
+fn render_binary(op: BinaryOp, lhs: Rendered, rhs: Rendered) -> Rendered {
+    let prec = match precedence(op) {
+        Some(prec) => prec,
+        None => {
+            let text = format!("{}({}, {})", op, lhs.text, rhs.text);
+            return Rendered::new(text, ATOM_PRECEDENCE);
+        }
+    };
+    let lhs_parens = lhs.prec < prec || (lhs.prec == prec && right_associative(op));
+    // A prefix `-` on the right reads unambiguously, even after `^`. At the
+    // same precedence only `a + (b + c)` and `a * (b * c)` may drop them,
+    // `2 * (7 // 2)` is not `2 * 7 // 2`
+    let regroups = associative(op) && rhs.op == Some(op);
+    let rhs_parens = (rhs.prec < prec && rhs.prec != NEG_PRECEDENCE)
+        || (rhs.prec == prec && !right_associative(op) && !regroups);
+    let text = format!(
+        "{} {} {}",
+        parenthesize(lhs, lhs_parens),
+        op,
+        parenthesize(rhs, rhs_parens)
+    );
+    Rendered {
+        text,
+        prec,
+        op: Some(op),
+    }
+}
+
+fn render_unary(op: UnaryOp, operand: Rendered) -> Rendered {
+    match op {
+        UnaryOp::Neg => {
+            // `--3` would read as a typo, keep nested negations apart
+            let needed = operand.prec <= NEG_PRECEDENCE;
+            Rendered::new(
+                format!("-{}", parenthesize(operand, needed)),
+                NEG_PRECEDENCE,
+            )
+        }
+        UnaryOp::Abs | UnaryOp::Sqrt => {
+            Rendered::new(format!("{}({})", op, operand.text), ATOM_PRECEDENCE)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::evaluate;
+    use super::*;
+
+    /// Renders `line` as infix, reads it back and checks both evaluate the
+    /// same.
+    fn round_trip(line: &str) -> String {
+        let infix = to_infix(line).unwrap();
+        let back = to_rpn(&infix).unwrap().join(" ");
+        assert_eq!(evaluate(line), evaluate(&back), "{} -> {}", line, infix);
+        infix
+    }
+
+    #[test]
+    fn keeps_parentheses_around_other_operators_of_the_same_precedence() {
+        assert_eq!(round_trip("2 7 2 // *"), "2 * (7 // 2)");
+        assert_eq!(round_trip("2 7 4 % *"), "2 * (7 % 4)");
+        assert_eq!(round_trip("2 7 4 / *"), "2 * (7 / 4)");
+        assert_eq!(round_trip("10 4 2 - -"), "10 - (4 - 2)");
+        assert_eq!(round_trip("1 4 2 - +"), "1 + (4 - 2)");
+        assert_eq!(round_trip("8 4 2 / /"), "8 / (4 / 2)");
+    }
+
+    #[test]
+    fn drops_parentheses_only_where_they_change_nothing() {
+        assert_eq!(round_trip("1 2 3 + +"), "1 + 2 + 3");
+        assert_eq!(round_trip("2 3 4 * *"), "2 * 3 * 4");
+        assert_eq!(round_trip("2 3 + 4 *"), "(2 + 3) * 4");
+        assert_eq!(round_trip("2 3 2 ^ ^"), "2 ^ 3 ^ 2");
+        assert_eq!(round_trip("2 3 ^ 2 ^"), "(2 ^ 3) ^ 2");
+        assert_eq!(round_trip("7 2 // 3 *"), "7 // 2 * 3");
+    }
+
+    #[test]
+    fn round_trips_functions_and_negation() {
+        round_trip("2 neg 2 ^");
+        round_trip("9 sqrt 3 4 max *");
+        round_trip("5 neg neg 2 min");
+    }
+
+    #[test]
+    fn rejects_commas_outside_calls() {
+        let error = to_rpn("(1,2)").unwrap_err();
+        assert_eq!(error.error, RpnError::MisplacedToken(",".to_string()));
+        assert!(to_rpn("1, 2").is_err());
+        assert_eq!(to_rpn("max((1), 2)").unwrap(), ["1", "2", "max"]);
+    }
+
+    #[test]
+    fn checks_the_arity_of_builtins() {
+        let error = to_rpn("sqrt(1,2)").unwrap_err();
+        assert_eq!(
+            error.error,
+            RpnError::ArgumentCount {
+                function: "sqrt".to_string(),
+                expected: 1,
+                found: 2
+            }
+        );
+        assert!(to_rpn("max(1)").is_err());
+        assert_eq!(to_rpn("f(1, 2, 3)").unwrap(), ["1", "2", "3", "f"]);
+    }
+}
@@ -0,0 +1,242 @@
+use super::errors::RpnError;
+use super::ops::{BinaryOp, UnaryOp};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+/// Largest power of ten accepted in exact literals, keeps `1e999999999` from
+/// allocating a gigantic integer.
+const MAX_EXPONENT: u32 = 1000;
+
+/// Largest result of `^` in exact mode, in bits of its numerator or
+/// denominator. Checked against the size of the base times the exponent, so
+/// `10 1000 ^ 1000 ^` fails before it is computed.
+const MAX_POWER_BITS: u64 = 1 << 16;
+
+/// A value the evaluator can keep on its stack.
+///
+/// Implementations decide how literals are parsed and how each operator
+/// behaves at the edges (overflow, division by zero).
+pub trait Number: Clone + Display + Sized {
+    fn parse(token: &str) -> Option<Self>;
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, RpnError>;
+    fn unary(op: UnaryOp, value: Self) -> Result<Self, RpnError>;
+}
+
+/// Fixed width integers, every operation is checked for overflow.
+impl Number for i64 {
+    fn parse(token: &str) -> Option<i64> {
+        token.parse().ok()
+    }
+
+    fn binary(op: BinaryOp, lhs: i64, rhs: i64) -> Result<i64, RpnError> {
+        let result = match op {
+            BinaryOp::Add => lhs.checked_add(rhs),
+            BinaryOp::Sub => lhs.checked_sub(rhs),
+            BinaryOp::Mul => lhs.checked_mul(rhs),
+            BinaryOp::Div | BinaryOp::IntDiv | BinaryOp::Rem if rhs == 0 => {
+                return Err(RpnError::DivisionByZero)
+            }
+            BinaryOp::Div | BinaryOp::IntDiv => lhs.checked_div(rhs),
+            BinaryOp::Rem => lhs.checked_rem(rhs),
+            BinaryOp::Pow => {
+                if rhs < 0 {
+                    return Err(RpnError::invalid_operand(
+                        op,
+                        "negative exponents have no integer result",
+                    ));
+                }
+                u32::try_from(rhs).ok().and_then(|exp| lhs.checked_pow(exp))
+            }
+            BinaryOp::Min => Some(lhs.min(rhs)),
+            BinaryOp::Max => Some(lhs.max(rhs)),
+        };
+        result.ok_or_else(|| RpnError::Overflow(op.to_string()))
+    }
+
+    fn unary(op: UnaryOp, value: i64) -> Result<i64, RpnError> {
+        let result = match op {
+            UnaryOp::Neg => value.checked_neg(),
+            UnaryOp::Abs => value.checked_abs(),
+            UnaryOp::Sqrt => {
+                if value < 0 {
+                    return Err(RpnError::invalid_operand(
+                        op,
+                        "negative numbers have no square root",
+                    ));
+                }
+                Some(value.isqrt())
+            }
+        };
+        result.ok_or_else(|| RpnError::Overflow(op.to_string()))
+    }
+}
+
+/// Arbitrary precision rationals. Decimal literals such as `19.99` or `1e-3`
+/// are read exactly, so no precision is lost over long chains of operations.
+impl Number for BigRational {
+    fn parse(token: &str) -> Option<BigRational> {
+        let (mantissa, exponent) = match token.find(['e', 'E']) {
+            Some(i) => (&token[..i], token[i + 1..].parse::<i32>().ok()?),
+            None => (token, 0),
+        };
+        if exponent.unsigned_abs() > MAX_EXPONENT {
+            return None;
+        }
+        let (int, frac) = match mantissa.find('.') {
+            Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+            None => (mantissa, ""),
+        };
+        // BigInt also accepts `_` between digits, which other modes do not
+        let digits = int.strip_prefix(['+', '-']).unwrap_or(int);
+        if !digits
+            .chars()
+            .chain(frac.chars())
+            .all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let numer: BigInt = format!("{}{}", int, frac).parse().ok()?;
+        let scale = BigInt::from(10u32).pow(frac.len());
+        let value = BigRational::new(numer, scale);
+
+        let shift = BigRational::from_integer(BigInt::from(10u32).pow(exponent.unsigned_abs()));
+        if exponent < 0 {
+            Some(value / shift)
+        } else {
+            Some(value * shift)
+        }
+    }
+
+    fn binary(op: BinaryOp, lhs: BigRational, rhs: BigRational) -> Result<BigRational, RpnError> {
+        match op {
+            BinaryOp::Add => Ok(lhs + rhs),
+            BinaryOp::Sub => Ok(lhs - rhs),
+            BinaryOp::Mul => Ok(lhs * rhs),
+            BinaryOp::Div | BinaryOp::IntDiv | BinaryOp::Rem => {
+                if rhs.is_zero() {
+                    return Err(RpnError::DivisionByZero);
+                }
+                let quotient = &lhs / &rhs;
+                Ok(match op {
+                    BinaryOp::Div => quotient,
+                    BinaryOp::IntDiv => quotient.trunc(),
+                    _ => lhs - rhs * quotient.trunc(),
+                })
+            }
+            BinaryOp::Pow => {
+                if !rhs.is_integer() {
+                    return Err(RpnError::invalid_operand(
+                        op,
+                        "exponent must be a whole number in exact mode",
+                    ));
+                }
+                // At least this many bits are added per multiplication, none
+                // for a base of 0, 1 or -1 which never grows
+                let bits = lhs.numer().bits().max(lhs.denom().bits()).saturating_sub(1);
+                let exp = rhs
+                    .to_integer()
+                    .to_i32()
+                    .filter(|exp| bits.saturating_mul(exp.unsigned_abs().into()) <= MAX_POWER_BITS)
+                    .ok_or_else(|| RpnError::Overflow(op.to_string()))?;
+                if exp < 0 && lhs.is_zero() {
+                    return Err(RpnError::DivisionByZero);
+                }
+                Ok(lhs.pow(exp))
+            }
+            BinaryOp::Min => Ok(lhs.min(rhs)),
+            BinaryOp::Max => Ok(lhs.max(rhs)),
+        }
+    }
+
+    fn unary(op: UnaryOp, value: BigRational) -> Result<BigRational, RpnError> {
+        match op {
+            UnaryOp::Neg => Ok(-value),
+            UnaryOp::Abs => Ok(value.abs()),
+            UnaryOp::Sqrt => {
+                if value.is_negative() {
+                    return Err(RpnError::invalid_operand(
+                        op,
+                        "negative numbers have no square root",
+                    ));
+                }
+                let numer = value.numer().sqrt();
+                let denom = value.denom().sqrt();
+                if &numer * &numer == *value.numer() && &denom * &denom == *value.denom() {
+                    Ok(BigRational::new(numer, denom))
+                } else {
+                    Err(RpnError::invalid_operand(
+                        op,
+                        "result is not an exact rational",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{evaluate, evaluate_as, evaluate_exact};
+    use super::*;
+
+    fn exact(n: i64, d: i64) -> BigRational {
+        BigRational::new(BigInt::from(n), BigInt::from(d))
+    }
+
+    #[test]
+    fn checked_mode_reports_overflow() {
+        for line in &[
+            "9223372036854775807 1 +",
+            "2 63 ^",
+            "-9223372036854775808 neg",
+        ] {
+            let op = line.rsplit(' ').next().unwrap().to_string();
+            assert_eq!(
+                evaluate(line),
+                Err(RpnError::Overflow(op.clone())),
+                "{}",
+                line
+            );
+            assert_eq!(
+                evaluate_as::<i64>(line),
+                Err(RpnError::Overflow(op)),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn exact_mode_keeps_decimals_exact() {
+        assert_eq!(evaluate_exact("0.1 0.2 +"), Ok(exact(3, 10)));
+        assert_eq!(evaluate_exact("1e-3"), Ok(exact(1, 1000)));
+        assert_eq!(evaluate_exact("2 -2 ^"), Ok(exact(1, 4)));
+    }
+
+    #[test]
+    fn exact_mode_refuses_huge_powers() {
+        assert!(evaluate_exact("10 1000 ^").is_ok());
+        assert_eq!(
+            evaluate_exact("10 1000 ^ 1000 ^"),
+            Err(RpnError::Overflow("^".to_string()))
+        );
+        assert_eq!(
+            evaluate_exact("2 3 / 100000 ^"),
+            Err(RpnError::Overflow("^".to_string()))
+        );
+        // A base of one stays small whatever the exponent
+        assert_eq!(evaluate_exact("1 1000000 ^"), Ok(exact(1, 1)));
+    }
+
+    #[test]
+    fn exact_literals_are_plain_numerals() {
+        for token in &["1_000", "1_0.5", "0._5", "1e1_0", "inf", "NaN", "0x10"] {
+            assert_eq!(BigRational::parse(token), None, "{}", token);
+        }
+        assert_eq!(BigRational::parse("-1.5"), Some(exact(-3, 2)));
+        assert_eq!(BigRational::parse("+2"), Some(exact(2, 1)));
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+
+/// Every word the evaluator understands besides numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Binary(BinaryOp),
+    Unary(UnaryOp),
+    Stack(StackOp),
+}
+
+/// Operators taking two numbers, the second one popped is the left operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// `//`, division truncated towards zero.
+    IntDiv,
+    /// `%`, remainder with the sign of the dividend.
+    Rem,
+    Pow,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Abs,
+    Sqrt,
+}
+
+/// Words that rearrange the stack without looking at the values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackOp {
+    Dup,
+    Swap,
+    Drop,
+    Over,
+    Rot,
+    Clear,
+}
+
+const WORDS: [(&str, Op); 18] = [
+    ("+", Op::Binary(BinaryOp::Add)),
+    ("-", Op::Binary(BinaryOp::Sub)),
+    ("*", Op::Binary(BinaryOp::Mul)),
+    ("/", Op::Binary(BinaryOp::Div)),
+    ("//", Op::Binary(BinaryOp::IntDiv)),
+    ("%", Op::Binary(BinaryOp::Rem)),
+    ("^", Op::Binary(BinaryOp::Pow)),
+    ("min", Op::Binary(BinaryOp::Min)),
+    ("max", Op::Binary(BinaryOp::Max)),
+    ("neg", Op::Unary(UnaryOp::Neg)),
+    ("abs", Op::Unary(UnaryOp::Abs)),
+    ("sqrt", Op::Unary(UnaryOp::Sqrt)),
+    ("dup", Op::Stack(StackOp::Dup)),
+    ("swap", Op::Stack(StackOp::Swap)),
+    ("drop", Op::Stack(StackOp::Drop)),
+    ("over", Op::Stack(StackOp::Over)),
+    ("rot", Op::Stack(StackOp::Rot)),
+    ("clear", Op::Stack(StackOp::Clear)),
+];
+
+impl Op {
+    pub fn parse(token: &str) -> Option<Op> {
+        WORDS
+            .iter()
+            .find(|(word, _)| *word == token)
+            .map(|(_, op)| *op)
+    }
+
+    pub fn symbol(self) -> &'static str {
+        WORDS
+            .iter()
+            .find(|(_, op)| *op == self)
+            .map(|(word, _)| *word)
+            .unwrap()
+    }
+
+    /// Number of values that must be on the stack before the word runs.
+    pub fn arity(self) -> usize {
+        match self {
+            Op::Binary(_) => 2,
+            Op::Unary(_) => 1,
+            Op::Stack(StackOp::Dup) | Op::Stack(StackOp::Drop) => 1,
+            Op::Stack(StackOp::Swap) | Op::Stack(StackOp::Over) => 2,
+            Op::Stack(StackOp::Rot) => 3,
+            Op::Stack(StackOp::Clear) => 0,
+        }
+    }
+}
+
+impl StackOp {
+    /// Rearranges `stack`, which must hold at least `Op::arity` values.
+    pub fn apply<T: Clone>(self, stack: &mut Vec<T>) {
+        let top = stack.len();
+        match self {
+            StackOp::Dup => stack.push(stack[top - 1].clone()),
+            StackOp::Swap => stack.swap(top - 1, top - 2),
+            StackOp::Drop => {
+                stack.pop();
+            }
+            StackOp::Over => stack.push(stack[top - 2].clone()),
+            StackOp::Rot => {
+                let third = stack.remove(top - 3);
+                stack.push(third);
+            }
+            StackOp::Clear => stack.clear(),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Op::Binary(*self).fmt(f)
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Op::Unary(*self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::errors::RpnError;
+    use super::super::evaluate;
+    use super::super::value::Value;
+    use super::*;
+
+    fn stack_after(op: StackOp, stack: &[i32]) -> Vec<i32> {
+        let mut stack = stack.to_vec();
+        op.apply(&mut stack);
+        stack
+    }
+
+    #[test]
+    fn rearranges_the_top_of_the_stack() {
+        assert_eq!(stack_after(StackOp::Dup, &[1, 2]), [1, 2, 2]);
+        assert_eq!(stack_after(StackOp::Swap, &[1, 2, 3]), [1, 3, 2]);
+        assert_eq!(stack_after(StackOp::Drop, &[1, 2]), [1]);
+        assert_eq!(stack_after(StackOp::Over, &[1, 2]), [1, 2, 1]);
+        assert_eq!(stack_after(StackOp::Rot, &[1, 2, 3, 4]), [1, 3, 4, 2]);
+        assert_eq!(stack_after(StackOp::Clear, &[1, 2]), []);
+    }
+
+    #[test]
+    fn stack_words_work_inside_lines() {
+        assert_eq!(evaluate("3 dup *"), Ok(Value::Int(9)));
+        assert_eq!(evaluate("1 2 swap -"), Ok(Value::Int(1)));
+        assert_eq!(evaluate("1 2 drop"), Ok(Value::Int(1)));
+        assert_eq!(evaluate("5 1 over - *"), Ok(Value::Int(-20)));
+        assert_eq!(evaluate("1 2 3 rot / -"), Ok(Value::Int(-1)));
+        assert_eq!(evaluate("1 2 clear 7"), Ok(Value::Int(7)));
+    }
+
+    #[test]
+    fn computes_each_operator() {
+        let cases = [
+            ("7 2 //", Value::Int(3)),
+            ("-7 2 //", Value::Int(-3)),
+            ("7.5 2 //", Value::Float(3.0)),
+            ("7 3 %", Value::Int(1)),
+            ("-7 3 %", Value::Int(-1)),
+            ("7 -3 %", Value::Int(1)),
+            ("2 8 ^", Value::Int(256)),
+            ("4 0.5 ^", Value::Float(2.0)),
+            ("5 neg", Value::Int(-5)),
+            ("-5 neg", Value::Int(5)),
+            ("-5 abs", Value::Int(5)),
+            ("-2.5 abs", Value::Float(2.5)),
+            ("81 sqrt", Value::Int(9)),
+            ("2.25 sqrt", Value::Float(1.5)),
+            ("3 -4 min", Value::Int(-4)),
+            ("3 -4 max", Value::Int(3)),
+            ("1.5 2 min", Value::Float(1.5)),
+        ];
+        for (line, expected) in &cases {
+            assert_eq!(evaluate(line), Ok(*expected), "{}", line);
+        }
+        assert_eq!(
+            evaluate("-1 sqrt"),
+            Err(RpnError::invalid_operand(
+                "sqrt",
+                "negative numbers have no square root"
+            ))
+        );
+    }
+
+    #[test]
+    fn each_word_checks_its_operands() {
+        for (word, op) in WORDS.iter().filter(|(_, op)| op.arity() > 0) {
+            let line = format!("{} {}", vec!["1"; op.arity() - 1].join(" "), word);
+            assert_eq!(
+                evaluate(&line),
+                Err(RpnError::StackUnderflow {
+                    op: word.to_string(),
+                    needed: op.arity(),
+                    found: op.arity() - 1,
+                }),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn words_name_themselves() {
+        for (word, op) in &WORDS {
+            assert_eq!(Op::parse(word), Some(*op));
+            assert_eq!(op.to_string(), *word);
+        }
+        assert_eq!(Op::parse("plus"), None);
+    }
+}
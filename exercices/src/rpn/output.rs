@@ -0,0 +1,227 @@
+use super::errors::Diagnostic;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// How batch results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `line 3: 124036` on the output, diagnostics on the error stream.
+    Text,
+    /// One JSON object per input line.
+    JsonLines,
+    /// A header row, then one row per input line.
+    Csv,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" | "jsonl" => Some(Format::JsonLines),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// The outcome of evaluating one input line.
+#[derive(Debug, Clone)]
+pub struct Record<'a> {
+    pub input: &'a str,
+    pub line: usize,
+    pub source: &'a str,
+    /// `Ok(None)` for lines that only define words or store variables.
+    pub outcome: Result<Option<String>, Diagnostic>,
+    /// Stack depth when the line finished or failed.
+    pub depth: usize,
+    /// The line rendered as infix, only used by the text format.
+    pub infix: Option<String>,
+}
+
+const CSV_HEADER: &str = "input,line,source,result,error,message,column,depth";
+
+/// Writes the header a format needs before its first record.
+pub fn write_header(format: Format, out: &mut impl Write) -> io::Result<()> {
+    match format {
+        Format::Csv => writeln!(out, "{}", CSV_HEADER),
+        Format::Text | Format::JsonLines => Ok(()),
+    }
+}
+
+/// Writes `record`, `prefix` names the input in text output. Only the text
+/// format writes to `err`, the others keep diagnostics in the record.
+pub fn write_record(
+    format: Format,
+    record: &Record,
+    prefix: &str,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> io::Result<()> {
+    match format {
+        Format::Text => write_text(record, prefix, out, err),
+        Format::JsonLines => write_json(record, out),
+        Format::Csv => write_csv(record, out),
+    }
+}
+
+fn write_text(
+    record: &Record,
+    prefix: &str,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> io::Result<()> {
+    match (&record.outcome, &record.infix) {
+        (Ok(Some(result)), Some(infix)) => {
+            writeln!(
+                out,
+                "{}line {}: {} = {}",
+                prefix, record.line, infix, result
+            )
+        }
+        (Ok(Some(result)), None) => writeln!(out, "{}line {}: {}", prefix, record.line, result),
+        (Ok(None), _) => Ok(()),
+        (Err(diagnostic), _) => {
+            writeln!(err, "{}{}", prefix, diagnostic.clone().on_line(record.line))
+        }
+    }
+}
+
+fn write_json(record: &Record, out: &mut impl Write) -> io::Result<()> {
+    let fields = fields(record);
+    let opt = |value: Option<String>| value.map_or("null".to_string(), |v| json_string(&v));
+    writeln!(
+        out,
+        "{{\"input\":{},\"line\":{},\"source\":{},\"result\":{},\"error\":{},\"message\":{},\"column\":{},\"depth\":{}}}",
+        json_string(record.input),
+        record.line,
+        json_string(record.source),
+        opt(fields.result),
+        opt(fields.error),
+        opt(fields.message),
+        fields.column.map_or("null".to_string(), |c| c.to_string()),
+        record.depth,
+    )
+}
+
+fn write_csv(record: &Record, out: &mut impl Write) -> io::Result<()> {
+    let fields = fields(record);
+    let opt = |value: Option<String>| value.map_or(String::new(), |v| csv_field(&v));
+    writeln!(
+        out,
+        "{},{},{},{},{},{},{},{}",
+        csv_field(record.input),
+        record.line,
+        csv_field(record.source),
+        opt(fields.result),
+        opt(fields.error),
+        opt(fields.message),
+        fields.column.map_or(String::new(), |c| c.to_string()),
+        record.depth,
+    )
+}
+
+/// The outcome split into columns shared by JSON and CSV.
+struct Fields {
+    result: Option<String>,
+    error: Option<String>,
+    message: Option<String>,
+    column: Option<usize>,
+}
+
+fn fields(record: &Record) -> Fields {
+    match &record.outcome {
+        Ok(result) => Fields {
+            result: result.clone(),
+            error: None,
+            message: None,
+            column: None,
+        },
+        Err(diagnostic) => Fields {
+            result: None,
+            error: Some(diagnostic.error.kind().to_string()),
+            message: Some(diagnostic.error.to_string()),
+            column: diagnostic.column,
+        },
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::errors::RpnError;
+    use super::*;
+
+    fn record(outcome: Result<Option<String>, Diagnostic>) -> Record<'static> {
+        Record {
+            input: "sample.txt",
+            line: 4,
+            source: "1 0 /",
+            outcome,
+            depth: 2,
+            infix: None,
+        }
+    }
+
+    #[test]
+    fn text_diagnostics_go_to_the_error_writer() {
+        let failed = record(Err(Diagnostic::new(RpnError::DivisionByZero, Some(5))));
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        write_record(Format::Text, &failed, "a: ", &mut out, &mut err).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            format!(
+                "a: {}\n",
+                Diagnostic::new(RpnError::DivisionByZero, Some(5)).on_line(4)
+            )
+        );
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        write_record(
+            Format::Text,
+            &record(Ok(Some("3".into()))),
+            "",
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+        assert_eq!(out, b"line 4: 3\n");
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn other_formats_keep_diagnostics_in_the_record() {
+        let failed = record(Err(Diagnostic::new(RpnError::DivisionByZero, Some(5))));
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        write_record(Format::Csv, &failed, "", &mut out, &mut err).unwrap();
+        assert!(err.is_empty());
+        assert!(String::from_utf8(out).unwrap().contains(",5,2"));
+    }
+}
@@ -0,0 +1,21 @@
+/// Splits a line on whitespace like `str::split_whitespace`, pairing each
+/// token with the 1-based character column it starts at.
+pub fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+
+    for (column, (i, c)) in line.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((column + 1, i)),
+            (true, Some((token_column, token_start))) => {
+                tokens.push((token_column, &line[token_start..i]));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some((token_column, token_start)) = start {
+        tokens.push((token_column, &line[token_start..]));
+    }
+    tokens
+}
@@ -0,0 +1,190 @@
+use super::errors::RpnError;
+use super::number::Number;
+use super::ops::{BinaryOp, UnaryOp};
+use std::fmt;
+
+/// A stack value that is either an integer or a float.
+///
+/// Integers stay integers as long as the result is exact, mixing an integer
+/// with a float promotes the integer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl Value {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(n) => n as f64,
+            Value::Float(x) => x,
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        match self {
+            Value::Int(n) => n == 0,
+            Value::Float(x) => x == 0.0,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            // Debug keeps the trailing `.0`, so floats never read as integers
+            Value::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value {
+        Value::Int(n)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Value {
+        Value::Float(x)
+    }
+}
+
+impl Number for Value {
+    fn parse(token: &str) -> Option<Value> {
+        if let Ok(n) = token.parse::<i64>() {
+            return Some(Value::Int(n));
+        }
+        // f64 also accepts words such as `inf` and `NaN`, only allow numerals
+        let numeral = token
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+        match token.parse::<f64>() {
+            Ok(x) if numeral && x.is_finite() => Some(Value::Float(x)),
+            _ => None,
+        }
+    }
+
+    fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, RpnError> {
+        let divides = matches!(op, BinaryOp::Div | BinaryOp::IntDiv | BinaryOp::Rem);
+        if divides && rhs.is_zero() {
+            return Err(RpnError::DivisionByZero);
+        }
+        if op == BinaryOp::Pow && lhs.is_zero() && rhs.as_f64() < 0.0 {
+            return Err(RpnError::DivisionByZero);
+        }
+
+        if let (Value::Int(a), Value::Int(b)) = (lhs, rhs) {
+            // Division and powers only stay integers when the result is exact
+            let inexact = match op {
+                BinaryOp::Div => matches!(a.checked_rem(b), Some(r) if r != 0),
+                BinaryOp::Pow => b < 0,
+                _ => false,
+            };
+            if !inexact {
+                return i64::binary(op, a, b).map(Value::Int);
+            }
+        }
+
+        let (a, b) = (lhs.as_f64(), rhs.as_f64());
+        let result = match op {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::IntDiv => (a / b).trunc(),
+            BinaryOp::Rem => a % b,
+            BinaryOp::Pow => a.powf(b),
+            BinaryOp::Min => a.min(b),
+            BinaryOp::Max => a.max(b),
+        };
+        float_result(op, result)
+    }
+
+    fn unary(op: UnaryOp, value: Value) -> Result<Value, RpnError> {
+        match value {
+            Value::Int(n) if op == UnaryOp::Sqrt && n >= 0 => {
+                let root = n.isqrt();
+                if root * root == n {
+                    Ok(Value::Int(root))
+                } else {
+                    Ok(Value::Float((n as f64).sqrt()))
+                }
+            }
+            Value::Int(n) => i64::unary(op, n).map(Value::Int),
+            Value::Float(x) => {
+                let result = match op {
+                    UnaryOp::Neg => -x,
+                    UnaryOp::Abs => x.abs(),
+                    UnaryOp::Sqrt if x < 0.0 => {
+                        return Err(RpnError::invalid_operand(
+                            op,
+                            "negative numbers have no square root",
+                        ))
+                    }
+                    UnaryOp::Sqrt => x.sqrt(),
+                };
+                float_result(op, result)
+            }
+        }
+    }
+}
+
+fn float_result(op: impl fmt::Display, result: f64) -> Result<Value, RpnError> {
+    if result.is_nan() {
+        Err(RpnError::invalid_operand(op, "result is not a real number"))
+    } else if result.is_infinite() {
+        Err(RpnError::Overflow(op.to_string()))
+    } else {
+        Ok(Value::Float(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::evaluate;
+    use super::*;
+
+    #[test]
+    fn integers_stay_integers_while_exact() {
+        assert_eq!(evaluate("2 3 +"), Ok(Value::Int(5)));
+        assert_eq!(evaluate("2 3 -"), Ok(Value::Int(-1)));
+        assert_eq!(evaluate("6 7 *"), Ok(Value::Int(42)));
+        assert_eq!(evaluate("8 2 /"), Ok(Value::Int(4)));
+        assert_eq!(evaluate("2 10 ^"), Ok(Value::Int(1024)));
+        assert_eq!(evaluate("16 sqrt"), Ok(Value::Int(4)));
+        assert_eq!(evaluate("7 2 //"), Ok(Value::Int(3)));
+    }
+
+    #[test]
+    fn inexact_results_become_floats() {
+        assert_eq!(evaluate("7 2 /"), Ok(Value::Float(3.5)));
+        assert_eq!(evaluate("2 -1 ^"), Ok(Value::Float(0.5)));
+        assert_eq!(evaluate("2 sqrt"), Ok(Value::Float(2f64.sqrt())));
+        assert_eq!(evaluate("1 0.5 +"), Ok(Value::Float(1.5)));
+        assert_eq!(evaluate("4 2.0 /"), Ok(Value::Float(2.0)));
+    }
+
+    #[test]
+    fn reads_decimal_and_exponent_literals() {
+        assert_eq!(Value::parse("42"), Some(Value::Int(42)));
+        assert_eq!(Value::parse("-7"), Some(Value::Int(-7)));
+        assert_eq!(Value::parse("1e-3"), Some(Value::Float(0.001)));
+        assert_eq!(Value::parse("2.5E2"), Some(Value::Float(250.0)));
+        assert_eq!(Value::parse(".5"), Some(Value::Float(0.5)));
+    }
+
+    #[test]
+    fn rejects_words_that_floats_accept() {
+        for token in &["inf", "-inf", "infinity", "NaN", "nan", "1e999", "1_000"] {
+            assert_eq!(Value::parse(token), None, "{}", token);
+        }
+    }
+
+    #[test]
+    fn floats_print_with_a_decimal_point() {
+        assert_eq!(Value::Int(3).to_string(), "3");
+        assert_eq!(Value::Float(3.0).to_string(), "3.0");
+    }
+}
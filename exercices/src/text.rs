@@ -0,0 +1,193 @@
+mod cli;
+mod concordance;
+mod stats;
+pub use cli::main;
+pub use concordance::{write_index, write_kwic, Concordance, Hit, Location, DEFAULT_STOP_WORDS};
+pub use stats::{grapheme_len, words, write_report, TextStats, WordCount};
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The poem shipped with the exercise, found regardless of the working
+/// directory. Also what the concordance indexes by default, the other
+/// sample text is RPN rather than prose.
+pub const DEFAULT_INPUT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/texts/Dickinson.txt");
+
+/// Settings of the text statistics.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Files read in order, `-` reads stdin.
+    pub inputs: Vec<String>,
+    /// How many of the longest and most frequent words to list.
+    pub top: usize,
+    /// Whether "The" and "the" count as different words.
+    pub case_sensitive: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            inputs: vec![DEFAULT_INPUT.to_string()],
+            top: 10,
+            case_sensitive: false,
+        }
+    }
+}
+
+/// Reads all configured inputs and prints statistics over them together.
+/// Returns `false` if an input could not be read.
+pub fn run(config: &Config) -> bool {
+    let mut stats = TextStats::new(config.case_sensitive);
+    let mut ok = true;
+    for input in &config.inputs {
+        match read_input(input) {
+            Ok(text) => stats.add(&text),
+            Err(err) => {
+                eprintln!("{}: {}", input, err);
+                ok = false;
+            }
+        }
+    }
+
+    let stdout = io::stdout();
+    if let Err(err) = write_report(&stats, config.top, &mut stdout.lock()) {
+        eprintln!("{}", err);
+        return false;
+    }
+    ok
+}
+
+/// The whole of a file, or of stdin for `-`.
+pub fn read_input(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        Ok(text)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// Words the concordance leaves out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopWords {
+    /// `DEFAULT_STOP_WORDS`.
+    Default,
+    None,
+    /// The words of a file.
+    File(String),
+}
+
+/// Settings of the concordance.
+#[derive(Debug, Clone)]
+pub struct ConcordanceConfig {
+    /// Files to index, and directories whose files are all indexed.
+    pub paths: Vec<String>,
+    /// Words to show in context, the whole index is printed when empty.
+    pub queries: Vec<String>,
+    /// Words shown either side of each hit.
+    pub context: usize,
+    pub case_sensitive: bool,
+    pub stop_words: StopWords,
+}
+
+impl Default for ConcordanceConfig {
+    fn default() -> Self {
+        ConcordanceConfig {
+            paths: vec![DEFAULT_INPUT.to_string()],
+            queries: Vec::new(),
+            context: 5,
+            case_sensitive: false,
+            stop_words: StopWords::Default,
+        }
+    }
+}
+
+/// Indexes the configured files and prints either each query in context
+/// or the whole index. Returns `false` if a file could not be read.
+pub fn concordance(config: &ConcordanceConfig) -> bool {
+    let file_words;
+    let stop_words: Vec<&str> = match &config.stop_words {
+        StopWords::Default => DEFAULT_STOP_WORDS.to_vec(),
+        StopWords::None => Vec::new(),
+        StopWords::File(path) => match fs::read_to_string(path) {
+            Ok(text) => {
+                file_words = text;
+                words(&file_words).collect()
+            }
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                return false;
+            }
+        },
+    };
+    let mut concordance = Concordance::new(config.case_sensitive, &stop_words);
+
+    let mut ok = true;
+    for path in &config.paths {
+        let files = match corpus_files(path) {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                ok = false;
+                continue;
+            }
+        };
+        for (name, file) in files {
+            match read_input(&file) {
+                Ok(text) => concordance.add(&name, &text),
+                Err(err) => {
+                    eprintln!("{}: {}", file, err);
+                    ok = false;
+                }
+            }
+        }
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = if config.queries.is_empty() {
+        write_index(&concordance, &mut out)
+    } else {
+        config
+            .queries
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, query)| {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                let hits = concordance.kwic(query, config.context);
+                if hits.is_empty() && concordance.is_stop_word(query) {
+                    eprintln!("{} is a stop word and not indexed", query);
+                } else if hits.is_empty() {
+                    eprintln!("{} was not found", query);
+                }
+                write_kwic(&concordance, &hits, &mut out)
+            })
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        return false;
+    }
+    ok
+}
+
+/// The files at `path` with the names they are shown under: a file as
+/// given, the files directly in a directory by file name, in order.
+fn corpus_files(path: &str) -> io::Result<Vec<(String, String)>> {
+    if path == "-" || !Path::new(path).is_dir() {
+        return Ok(vec![(path.to_string(), path.to_string())]);
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            files.push((name, entry.path().to_string_lossy().into_owned()));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,134 @@
+use super::{concordance, run, ConcordanceConfig, Config, StopWords};
+
+const USAGE: &str = "\
+usage: exercices text [OPTIONS] [FILE]...
+       exercices text concordance [OPTIONS] [PATH]...
+
+Counts the lines, sentences and words of the given files taken together,
+`-` or no FILE reads stdin. Words follow Unicode word boundaries, so
+punctuation is left out, and their lengths are counted in characters as
+a reader sees them.
+
+`concordance` indexes every word of the given files and of the files in
+the given directories (default src/texts/Dickinson.txt), then prints
+where each word occurs, or with --kwic each hit of a word among the
+words around it.
+
+options:
+    --top N             list the N longest and most frequent words
+                        (default 10)
+    --case-sensitive    count \"The\" and \"the\" as different words
+    --kwic WORD         show WORD in context, may be repeated
+    --context N         words shown either side of a hit (default 5)
+    --stop-words FILE   leave out the words in FILE instead of common
+                        English ones
+    --no-stop-words     index every word";
+
+/// Entry point of `exercices text`, returns the process exit status.
+pub fn main(args: &[String]) -> i32 {
+    if args.first().map(String::as_str) == Some("concordance") {
+        return concordance_main(&args[1..]);
+    }
+    match parse_args(args) {
+        Ok(Some(config)) => {
+            if run(&config) {
+                0
+            } else {
+                1
+            }
+        }
+        Ok(None) => {
+            println!("{}", USAGE);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            2
+        }
+    }
+}
+
+/// `Ok(None)` when help was asked for.
+fn parse_args(args: &[String]) -> Result<Option<Config>, String> {
+    let mut config = Config {
+        inputs: Vec::new(),
+        ..Config::default()
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--top" => {
+                config.top = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| "--top needs a number".to_string())?
+            }
+            "--case-sensitive" => config.case_sensitive = true,
+            "-h" | "--help" => return Ok(None),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            input => config.inputs.push(input.to_string()),
+        }
+    }
+
+    if config.inputs.is_empty() {
+        config.inputs.push("-".to_string());
+    }
+    Ok(Some(config))
+}
+
+fn concordance_main(args: &[String]) -> i32 {
+    match parse_concordance_args(args) {
+        Ok(Some(config)) => {
+            if concordance(&config) {
+                0
+            } else {
+                1
+            }
+        }
+        Ok(None) => {
+            println!("{}", USAGE);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            2
+        }
+    }
+}
+
+/// `Ok(None)` when help was asked for.
+fn parse_concordance_args(args: &[String]) -> Result<Option<ConcordanceConfig>, String> {
+    let mut config = ConcordanceConfig {
+        paths: Vec::new(),
+        ..ConcordanceConfig::default()
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match arg.as_str() {
+            "--kwic" => config.queries.push(value(arg)?),
+            "--context" => {
+                config.context = value(arg)?
+                    .parse()
+                    .map_err(|_| "--context must be a number".to_string())?
+            }
+            "--case-sensitive" => config.case_sensitive = true,
+            "--stop-words" => config.stop_words = StopWords::File(value(arg)?),
+            "--no-stop-words" => config.stop_words = StopWords::None,
+            "-h" | "--help" => return Ok(None),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            path => config.paths.push(path.to_string()),
+        }
+    }
+
+    if config.paths.is_empty() {
+        config.paths = ConcordanceConfig::default().paths;
+    }
+    Ok(Some(config))
+}
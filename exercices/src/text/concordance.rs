@@ -0,0 +1,298 @@
+use super::stats::grapheme_len;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Common English words left out of the index unless configured otherwise.
+pub const DEFAULT_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "had", "has", "have",
+    "he", "her", "his", "i", "if", "in", "is", "it", "its", "me", "my", "no", "not", "of", "on",
+    "or", "our", "she", "so", "that", "the", "their", "them", "then", "there", "they", "this",
+    "to", "too", "was", "we", "were", "what", "which", "who", "will", "with", "you", "your",
+];
+
+/// Where a word was found, line and column counted from 1, columns in
+/// characters as a reader sees them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    /// Index into `Concordance::files`.
+    pub file: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A word of a text as written.
+#[derive(Debug, Clone)]
+struct Token {
+    word: String,
+    location: Location,
+}
+
+impl Token {
+    fn text(&self) -> &str {
+        &self.word
+    }
+}
+
+/// One occurrence of a word with the words around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit<'a> {
+    pub location: Location,
+    pub before: Vec<&'a str>,
+    pub word: &'a str,
+    pub after: Vec<&'a str>,
+}
+
+/// An inverted index from each word of a corpus to everywhere it occurs.
+#[derive(Debug, Clone, Default)]
+pub struct Concordance {
+    /// Whether "The" and "the" are indexed apart.
+    case_sensitive: bool,
+    /// Lowercase words that are not indexed.
+    stop_words: HashSet<String>,
+    files: Vec<String>,
+    /// The words of each file in order, for context.
+    tokens: Vec<Vec<Token>>,
+    /// By the word as folded, the file and token index of each occurrence.
+    index: BTreeMap<String, Vec<(usize, usize)>>,
+}
+
+impl Concordance {
+    /// Stop words match whatever their case.
+    pub fn new(case_sensitive: bool, stop_words: &[&str]) -> Concordance {
+        Concordance {
+            case_sensitive,
+            stop_words: stop_words.iter().map(|word| word.to_lowercase()).collect(),
+            ..Concordance::default()
+        }
+    }
+
+    /// Indexes `text` under the file name `name`.
+    pub fn add(&mut self, name: &str, text: &str) {
+        let file = self.files.len();
+        let mut tokens = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            // Counted on from the previous word, not from the line start
+            let (mut counted, mut column) = (0, 1);
+            for (offset, word) in line.unicode_word_indices() {
+                column += grapheme_len(&line[counted..offset]);
+                counted = offset;
+                let location = Location {
+                    file,
+                    line: number + 1,
+                    column,
+                };
+                if !self.stop_words.contains(&word.to_lowercase()) {
+                    self.index
+                        .entry(self.fold(word))
+                        .or_default()
+                        .push((file, tokens.len()));
+                }
+                tokens.push(Token {
+                    word: word.to_string(),
+                    location,
+                });
+            }
+        }
+        self.files.push(name.to_string());
+        self.tokens.push(tokens);
+    }
+
+    fn fold(&self, word: &str) -> String {
+        if self.case_sensitive {
+            word.to_string()
+        } else {
+            word.to_lowercase()
+        }
+    }
+
+    /// The names the files were added under.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    pub fn is_stop_word(&self, word: &str) -> bool {
+        self.stop_words.contains(&word.to_lowercase())
+    }
+
+    /// Every indexed word with its number of occurrences, in alphabetical
+    /// order.
+    pub fn words(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.index
+            .iter()
+            .map(|(word, occurrences)| (word.as_str(), occurrences.len()))
+    }
+
+    /// Where `word` occurs, in file then reading order.
+    pub fn locations(&self, word: &str) -> Vec<Location> {
+        self.occurrences(word)
+            .iter()
+            .map(|&(file, i)| self.tokens[file][i].location)
+            .collect()
+    }
+
+    /// Each occurrence of `word` with up to `context` words either side,
+    /// taken across line breaks but not across files.
+    pub fn kwic(&self, word: &str, context: usize) -> Vec<Hit<'_>> {
+        self.occurrences(word)
+            .iter()
+            .map(|&(file, i)| {
+                let tokens = &self.tokens[file];
+                Hit {
+                    location: tokens[i].location,
+                    before: tokens[i.saturating_sub(context)..i]
+                        .iter()
+                        .map(Token::text)
+                        .collect(),
+                    word: tokens[i].text(),
+                    after: tokens[i + 1..(i + 1 + context).min(tokens.len())]
+                        .iter()
+                        .map(Token::text)
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    fn occurrences(&self, word: &str) -> &[(usize, usize)] {
+        self.index
+            .get(&self.fold(word))
+            .map_or(&[], |occurrences| occurrences.as_slice())
+    }
+
+    /// `file:line:column`, the way compilers and grep point at text.
+    pub fn describe(&self, location: Location) -> String {
+        format!(
+            "{}:{}:{}",
+            self.files[location.file], location.line, location.column
+        )
+    }
+}
+
+/// Prints every indexed word with its count and locations.
+pub fn write_index(concordance: &Concordance, out: &mut impl Write) -> io::Result<()> {
+    for (word, count) in concordance.words() {
+        let locations: Vec<String> = concordance
+            .locations(word)
+            .into_iter()
+            .map(|location| concordance.describe(location))
+            .collect();
+        writeln!(out, "{} ({}): {}", word, count, locations.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Prints the hits one per line, keywords lined up in a column.
+pub fn write_kwic(concordance: &Concordance, hits: &[Hit], out: &mut impl Write) -> io::Result<()> {
+    let places: Vec<String> = hits
+        .iter()
+        .map(|hit| concordance.describe(hit.location))
+        .collect();
+    let befores: Vec<String> = hits.iter().map(|hit| hit.before.join(" ")).collect();
+    let place_width = places.iter().map(|place| grapheme_len(place)).max();
+    let before_width = befores.iter().map(|before| grapheme_len(before)).max();
+
+    for ((hit, place), before) in hits.iter().zip(&places).zip(&befores) {
+        // Padded by graphemes, `format!` widths count code points
+        let line = format!(
+            "{}{}  {}{} [{}] {}",
+            place,
+            " ".repeat(place_width.unwrap_or(0) - grapheme_len(place)),
+            " ".repeat(before_width.unwrap_or(0) - grapheme_len(before)),
+            before,
+            hit.word,
+            hit.after.join(" ")
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn concordance(case_sensitive: bool) -> Concordance {
+        let mut concordance = Concordance::new(case_sensitive, DEFAULT_STOP_WORDS);
+        concordance.add(
+            "a.txt",
+            "The Bird came down the Walk,\nhe did not know I saw;",
+        );
+        concordance.add(
+            "b.txt",
+            "Hope is the thing with feathers\nthat perches in the bird",
+        );
+        concordance
+    }
+
+    #[test]
+    fn locates_words_in_characters() {
+        let mut concordance = Concordance::new(false, &[]);
+        concordance.add("é.txt", "Café crème, café noir");
+        let places: Vec<String> = concordance
+            .locations("CAFÉ")
+            .into_iter()
+            .map(|location| concordance.describe(location))
+            .collect();
+        assert_eq!(places, ["é.txt:1:1", "é.txt:1:13"]);
+
+        // An accent written as a combining mark is still one column
+        concordance.add("e.txt", "cafe\u{301} noir cafe\u{301} noir");
+        let columns: Vec<usize> = concordance
+            .locations("noir")
+            .iter()
+            .map(|location| location.column)
+            .collect();
+        assert_eq!(columns, [18, 6, 16]);
+    }
+
+    #[test]
+    fn shows_hits_in_context_within_each_file() {
+        let concordance = concordance(false);
+        let hits = concordance.kwic("bird", 2);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].before, ["The"]);
+        assert_eq!(hits[0].word, "Bird");
+        assert_eq!(hits[0].after, ["came", "down"]);
+        // Context runs across lines but stops at the end of the file
+        assert_eq!(
+            hits[1].location,
+            Location {
+                file: 1,
+                line: 2,
+                column: 21
+            }
+        );
+        assert_eq!(hits[1].before, ["in", "the"]);
+        assert!(hits[1].after.is_empty());
+
+        let hits = concordance.kwic("walk", 3);
+        assert_eq!(hits[0].after, ["he", "did", "not"]);
+    }
+
+    #[test]
+    fn folds_case_and_leaves_out_stop_words() {
+        assert_eq!(concordance(true).locations("bird").len(), 1);
+        assert_eq!(concordance(false).locations("Bird").len(), 2);
+
+        let concordance = concordance(false);
+        assert!(concordance.locations("the").is_empty());
+        assert!(concordance.is_stop_word("The"));
+        assert!(concordance
+            .words()
+            .all(|(word, _)| !concordance.is_stop_word(word)));
+        // Stop words still count as context
+        assert_eq!(concordance.kwic("hope", 2)[0].after, ["is", "the"]);
+    }
+
+    #[test]
+    fn lines_up_keywords() {
+        let concordance = concordance(false);
+        let mut out = Vec::new();
+        write_kwic(&concordance, &concordance.kwic("bird", 2), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let columns: Vec<usize> = text.lines().map(|line| line.find('[').unwrap()).collect();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[0], columns[1]);
+    }
+}
@@ -0,0 +1,313 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The words of `text` along Unicode word boundaries (UAX #29), leaving out
+/// punctuation and spaces. Apostrophes inside a word keep it whole, so
+/// "don't" is one word.
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.unicode_words()
+}
+
+/// The length of `word` as a reader counts it, in grapheme clusters: "é"
+/// is one whether written as one code point or two.
+pub fn grapheme_len(word: &str) -> usize {
+    word.graphemes(true).count()
+}
+
+/// A distinct word and how often it was seen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordCount {
+    /// The word as first written.
+    pub word: String,
+    /// In grapheme clusters.
+    pub length: usize,
+    pub count: usize,
+}
+
+/// Word, line and sentence counts over one or more texts.
+#[derive(Debug, Clone, Default)]
+pub struct TextStats {
+    /// Whether "The" and "the" are different words.
+    case_sensitive: bool,
+    /// Distinct words in order of first appearance.
+    entries: Vec<WordCount>,
+    /// Index into `entries` by the word, lowercased unless case sensitive.
+    index: HashMap<String, usize>,
+    words: usize,
+    lines: usize,
+    sentences: usize,
+    /// Number of words of each length.
+    lengths: BTreeMap<usize, usize>,
+}
+
+impl TextStats {
+    pub fn new(case_sensitive: bool) -> TextStats {
+        TextStats {
+            case_sensitive,
+            ..TextStats::default()
+        }
+    }
+
+    /// Counts everything in `text` on top of what was added before.
+    pub fn add(&mut self, text: &str) {
+        self.lines += text.lines().count();
+        self.sentences += count_sentences(text);
+
+        for word in words(text) {
+            let key = if self.case_sensitive {
+                word.to_string()
+            } else {
+                word.to_lowercase()
+            };
+            let length = grapheme_len(word);
+            match self.index.get(&key) {
+                Some(&i) => self.entries[i].count += 1,
+                None => {
+                    self.index.insert(key, self.entries.len());
+                    self.entries.push(WordCount {
+                        word: word.to_string(),
+                        length,
+                        count: 1,
+                    });
+                }
+            }
+            self.words += 1;
+            *self.lengths.entry(length).or_insert(0) += 1;
+        }
+    }
+
+    /// Words counted, repeats included.
+    pub fn words(&self) -> usize {
+        self.words
+    }
+
+    pub fn distinct_words(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    pub fn sentences(&self) -> usize {
+        self.sentences
+    }
+
+    /// Number of words of each length, repeats included.
+    pub fn histogram(&self) -> &BTreeMap<usize, usize> {
+        &self.lengths
+    }
+
+    /// Every word of the greatest length, in order of first appearance.
+    pub fn longest(&self) -> Vec<&WordCount> {
+        let max = self.entries.iter().map(|entry| entry.length).max();
+        self.entries
+            .iter()
+            .filter(|entry| Some(entry.length) == max)
+            .collect()
+    }
+
+    /// The `n` longest distinct words, longest first, ties in order of
+    /// first appearance.
+    pub fn top_longest(&self, n: usize) -> Vec<&WordCount> {
+        let mut entries: Vec<&WordCount> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| Reverse(entry.length));
+        entries.truncate(n);
+        entries
+    }
+
+    /// The `n` most frequent words, ties in order of first appearance.
+    pub fn most_frequent(&self, n: usize) -> Vec<&WordCount> {
+        let mut entries: Vec<&WordCount> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| Reverse(entry.count));
+        entries.truncate(n);
+        entries
+    }
+}
+
+/// Sentences along Unicode sentence boundaries that hold at least one
+/// word. Line breaks within a paragraph do not end a sentence, so verse
+/// and wrapped prose run on until a full stop, blank lines do.
+fn count_sentences(text: &str) -> usize {
+    let mut count = 0;
+    let mut paragraph = String::new();
+    for line in text.lines().chain(std::iter::once("")) {
+        if line.trim().is_empty() {
+            // Skipping "" matters: `filter` asks the sentence iterator for
+            // its size_hint, which underflows on an empty string
+            if paragraph.is_empty() {
+                continue;
+            }
+            count += paragraph
+                .unicode_sentences()
+                .filter(|sentence| words(sentence).next().is_some())
+                .count();
+            paragraph.clear();
+        } else {
+            paragraph.push_str(line);
+            paragraph.push(' ');
+        }
+    }
+    count
+}
+
+/// Widest bar of the length histogram.
+const BAR_WIDTH: usize = 40;
+
+/// Prints the counts, the longest words with any ties, the `top` longest
+/// and most frequent words and a histogram of word lengths.
+pub fn write_report(stats: &TextStats, top: usize, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "Lines: {}", stats.lines())?;
+    writeln!(out, "Sentences: {}", stats.sentences())?;
+    writeln!(
+        out,
+        "Words: {} ({} distinct)",
+        stats.words(),
+        stats.distinct_words()
+    )?;
+    let longest = stats.longest();
+    if let Some(first) = longest.first() {
+        let words: Vec<&str> = longest.iter().map(|entry| entry.word.as_str()).collect();
+        writeln!(
+            out,
+            "Longest word{} ({} characters): {}",
+            if words.len() > 1 { "s" } else { "" },
+            first.length,
+            words.join(", ")
+        )?;
+    }
+    if top == 0 || stats.words() == 0 {
+        return Ok(());
+    }
+
+    let width = stats
+        .top_longest(top)
+        .iter()
+        .chain(stats.most_frequent(top).iter())
+        .map(|entry| entry.length)
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    writeln!(out, "\nLongest words")?;
+    for (rank, entry) in stats.top_longest(top).iter().enumerate() {
+        write_entry(out, rank, entry, entry.length, width)?;
+    }
+    writeln!(out, "\nMost frequent words")?;
+    for (rank, entry) in stats.most_frequent(top).iter().enumerate() {
+        write_entry(out, rank, entry, entry.count, width)?;
+    }
+
+    writeln!(out, "\nWord lengths")?;
+    let most = stats.histogram().values().copied().max().unwrap_or(1);
+    for (length, &count) in stats.histogram() {
+        // Every length seen gets at least one mark
+        let bar = (count * BAR_WIDTH).div_ceil(most);
+        writeln!(
+            out,
+            "{:>4} {:<width$} {}",
+            length,
+            "#".repeat(bar),
+            count,
+            width = BAR_WIDTH
+        )?;
+    }
+    Ok(())
+}
+
+/// Pads by graphemes rather than bytes, so columns line up past ASCII.
+fn write_entry(
+    out: &mut impl Write,
+    rank: usize,
+    entry: &WordCount,
+    value: usize,
+    width: usize,
+) -> io::Result<()> {
+    let padding = " ".repeat(width.saturating_sub(entry.length));
+    writeln!(
+        out,
+        "{:>4}  {}{} {:>6}",
+        rank + 1,
+        entry.word,
+        padding,
+        value
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(text: &str) -> TextStats {
+        let mut stats = TextStats::default();
+        stats.add(text);
+        stats
+    }
+
+    #[test]
+    fn counts_graphemes_not_code_points() {
+        assert_eq!(grapheme_len("caf\u{e9}"), 4);
+        assert_eq!(grapheme_len("cafe\u{301}"), 4);
+        assert_eq!(grapheme_len("\u{1f1eb}\u{1f1f7}"), 1);
+        assert_eq!(
+            words("Don't stop, naïve café!").collect::<Vec<_>>(),
+            ["Don't", "stop", "naïve", "café"]
+        );
+    }
+
+    #[test]
+    fn keeps_every_word_of_the_greatest_length() {
+        let stats = stats("Bright Summer, bright sunset; and nights");
+        let longest: Vec<&str> = stats
+            .longest()
+            .iter()
+            .map(|entry| entry.word.as_str())
+            .collect();
+        assert_eq!(longest, ["Bright", "Summer", "sunset", "nights"]);
+        assert_eq!(stats.longest()[0].count, 2);
+
+        let top: Vec<&str> = stats
+            .top_longest(2)
+            .iter()
+            .map(|entry| entry.word.as_str())
+            .collect();
+        assert_eq!(top, ["Bright", "Summer"]);
+        let frequent: Vec<&str> = stats
+            .most_frequent(2)
+            .iter()
+            .map(|entry| entry.word.as_str())
+            .collect();
+        assert_eq!(frequent, ["Bright", "Summer"]);
+    }
+
+    #[test]
+    fn folds_case_unless_asked() {
+        let mut sensitive = TextStats::new(true);
+        sensitive.add("The the THE");
+        assert_eq!(sensitive.distinct_words(), 3);
+        assert_eq!(stats("The the THE").distinct_words(), 1);
+    }
+
+    #[test]
+    fn counts_words_of_each_length() {
+        let stats = stats("a bb cc é ddd");
+        let histogram: Vec<(usize, usize)> = stats
+            .histogram()
+            .iter()
+            .map(|(&length, &count)| (length, count))
+            .collect();
+        assert_eq!(histogram, [(1, 2), (2, 2), (3, 1)]);
+        assert_eq!(stats.words(), 5);
+    }
+
+    #[test]
+    fn counts_sentences_across_lines_and_paragraphs() {
+        let text = "\nHope is the thing\nwith feathers.\nIt sings!\n\n\nA new stanza\n\n...\n";
+        let stats = stats(text);
+        assert_eq!(stats.sentences(), 3);
+        assert_eq!(stats.lines(), 9);
+        assert_eq!(count_sentences(""), 0);
+        assert_eq!(count_sentences("\n\n"), 0);
+    }
+}
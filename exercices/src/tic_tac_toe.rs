@@ -1,224 +1,234 @@
+mod agent;
+mod ai;
+mod analysis;
+mod cli;
 mod errors;
-use std::fmt::{self, Display, Formatter};
+mod game;
+mod input;
+mod mcts;
+mod net;
+mod record;
+mod rng;
+mod score;
+mod session;
+mod tournament;
+mod tui;
+mod ultimate;
+pub use agent::{Agent, Human, Scripted};
+pub use ai::{Ai, Difficulty, PERFECT_MAX_SQUARES};
+pub use analysis::{
+    write_book, write_stats, Analysis, BookEntry, Counts, DepthStats, FirstMove,
+    ANALYSIS_MAX_SQUARES,
+};
+pub use cli::main;
+pub use errors::{InputError, MoveError, RecordError};
+pub use game::{Game, Move, Outcome, Player, Rules, MAX_SQUARES};
+pub use input::{parse_move, parse_ultimate_move};
+pub use mcts::{Budget, Mcts, MAX_THREADS};
+pub use net::{connect, serve, DEFAULT_PORT};
+pub use record::{parse_session, write_session, GameRecord};
+pub use rng::Rng;
+pub use score::Score;
+pub use tournament::{
+    expected, play_game, write_results, Pairing, Standing, Tournament, INITIAL_RATING,
+};
+pub use ultimate::Ultimate;
+
+use session::{load_session, Session};
 use std::io;
 
-struct Board {
-    matrix: [[Option<char>; 3]; 3],
-    score: (u32, u32),
-    round: (u32),
+/// Settings for a session in the terminal.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub rules: Rules,
+    /// The computer's strength, `None` for one human playing both sides.
+    pub ai: Option<Difficulty>,
+    /// The side the computer plays.
+    pub ai_plays: Player,
+    /// Makes the computer's choices reproducible.
+    pub seed: Option<u64>,
+    /// Search time of tree search, see `Ai::with_budget`.
+    pub budget: Option<Budget>,
+    /// Threads used by tree search, see `Ai::with_threads`.
+    pub threads: Option<usize>,
+    /// Session file to pick up from, an unfinished last game is resumed.
+    pub load: Option<String>,
+    /// Session file rewritten after every game and on quitting.
+    pub save: Option<String>,
+    /// Full-screen play with a cursor instead of typed squares.
+    pub tui: bool,
 }
 
-impl Display for Board {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        println!("\nScore:\n\tX: {}\n\tO: {}", self.score.0, self.score.1);
-        println!(
-            "Game: {}, round: {}",
-            self.score.0 + self.score.1 + 1,
-            self.round
-        );
-        // TODO: Iterate over matrix instead of using range for scalability
-        for i in 0..3 {
-            println!("-------------");
-            for j in 0..3 {
-                print!("|");
-                let square = self.matrix[i][j];
-                match square {
-                    None => print!("   "),
-                    Some(val) => print!(" {} ", val),
-                }
-            }
-            print!("|\n");
-        }
-        println!("-------------");
-        write!(f, "")
-    }
-}
-
-impl Board {
-    fn new() -> Board {
-        println!("\n\n\n\t\t\tTIC TAC TOE YO");
-        println!("To play, specify the square using row,col");
-        println!("Example: to play in the top right square, enter '1,3'");
-        Board {
-            matrix: [[None; 3]; 3],
-            score: (0, 0),
-            round: 1,
-        }
-    }
-
-    fn play(&mut self, input: &mut String, player: &mut char) -> Result<(i8, i8), errors::MyErr> {
-        // Extract to proper coordinates
-        // Validate nr of inputs
-        let mut v: Vec<String> = input.split(',').map(|s| String::from(s)).collect();
-
-        if v.len() > 2 {
-            return Err(errors::MyErr::new("Error: Too many inputs"));
-        }
-
-        // TODO: Replace replace with trim
-        v[1] = v[1].replace('\n', "");
-
-        // println!("Vector contains: {} and {}", v[0], v[1]);
-        // println!("{:?}, {:?}", v[0].parse::<i8>(), v[1].parse::<i8>());
-        // println!("{:?}, {:?}", v.pop(), v.pop());
-
-        // TODO: Combine both if let into one
-        let (mut cx, mut cy): (i8, i8) = (-1, -1);
-        if let Ok(value) = v[0].parse::<i8>() {
-            cx = value - 1;
-        } else {
-            return Err(errors::MyErr::new(
-                "Error: could not parse first val to a valid index\nPlease try again",
-            ));
-        }
-
-        if let Ok(value) = v[1].parse::<i8>() {
-            cy = value - 1;
-        } else {
-            return Err(errors::MyErr::new(
-                "Error: could not parse first val to a valid index\nPlease try again",
-            ));
-        }
-
-        // println!("PARSED INPUT: row {}, col {}", cx, cy);
-
-        // Do input validation
-        // Attempt legal move
-        if (cx < 3 && cx >= 0) && (cy < 3 && cy >= 0) {
-            if self.matrix[cx as usize][cy as usize] != None {
-                return Err(errors::MyErr::new("Square already occupied dude..."));
-            } else {
-                self.matrix[cx as usize][cy as usize] = Some(*player);
-                self.round += 1;
-                Ok((cx, cy))
-            }
-        } else {
-            Err(errors::MyErr::new(
-                "Entered square is out of range\nPlease try again",
-            ))
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rules: Rules::default(),
+            ai: None,
+            ai_plays: Player::O,
+            seed: None,
+            budget: None,
+            threads: None,
+            load: None,
+            save: None,
+            tui: false,
         }
     }
 }
 
-pub fn run() {
+/// Plays games until stdin is closed. Returns `false` if the session could
+/// not be loaded or saved.
+pub fn run(config: &Config) -> bool {
     /*
     * FLOW:
-    Player plays as both X and Y.
+    Player plays as both X and Y, unless the computer takes one side.
     Draw board
-    Wait for user input
-    Validate input, then either re-prompt or write to board
+    Wait for user input, or let the computer pick a square
+    Parse input, then either re-prompt or hand the move to the game
     Check if game over
     */
-    let alternate_player = |player: char| -> char {
-        if player == 'O' {
-            'X'
-        } else {
-            'O'
+    let mut session = match Session::start(config) {
+        Ok(session) => session,
+        Err(message) => {
+            eprintln!("{}", message);
+            return false;
         }
     };
 
-    let mut board: Board = Board::new();
-    let mut curr_player: char = 'X';
+    let rules = config.rules;
+    println!("\n\n\n\t\t\tTIC TAC TOE YO");
+    if rules != Rules::tic_tac_toe() {
+        println!(
+            "{}x{} board, {} in a row wins",
+            rules.width, rules.height, rules.win_length
+        );
+    }
+    println!("To play, specify the square using row,col or column letter and row");
+    if rules == Rules::tic_tac_toe() {
+        println!("Example: to play in the top right square, enter '1,3', 'c1' or keypad '9'");
+    } else {
+        println!("Example: to play in the top left square, enter '1,1' or 'a1'");
+    }
+    if let Some(ai) = session.ai() {
+        println!(
+            "The computer plays {} ({})",
+            config.ai_plays,
+            ai.difficulty()
+        );
+    }
 
     loop {
-        println!("{}", board);
-        println!("{}'s turn to play", curr_player);
-
-        // Read input
-        let mut input = String::new();
-        io::stdin().read_line(&mut input);
+        let game = session.game();
+        println!("\n{}", session.score());
+        println!(
+            "Game: {}, round: {}",
+            session.score().games() + 1,
+            game.history().len() + 1
+        );
+        println!("{}", game);
+        println!("{}'s turn to play", game.to_move());
+
+        let square = if session.computer_to_move() {
+            let square = session
+                .computer_move()
+                .expect("an ongoing game has a free square");
+            println!("The computer plays {}", square);
+            square
+        } else {
+            // Read input, stop once stdin is closed
+            let mut input = String::new();
+            match io::stdin().read_line(&mut input) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => (),
+            }
 
-        // Pass to board, which will take care of input validation
-        let last_move: (i8, i8);
-        match board.play(&mut input, &mut curr_player) {
-            Ok((x, y)) => last_move = (x, y),
-            Err(e) => {
-                println!("{}", e);
-                continue;
+            match parse_move(&input, game.rules()) {
+                Ok(square) => square,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
             }
-        }
+        };
 
-        if game_is_over(&board, last_move, &curr_player) == "YES" {
-            println!("{}", board);
-            end_game(&curr_player, &mut board);
-        } else if game_is_over(&board, last_move, &curr_player) == "DRAW" {
-            println!("{}", board);
-            end_game(&'D', &mut board);
+        match session.play(square) {
+            Ok(Outcome::Ongoing) => (),
+            Ok(outcome) => {
+                if let Some(game) = session.last_game() {
+                    println!("{}", game);
+                }
+                end_game(outcome);
+            }
+            Err(e) => println!("{}", e),
         }
-        curr_player = alternate_player(curr_player);
     }
+    println!("\nSession over\n{}", session.score());
+    session.finish()
 }
 
-// TODO: Both functions should perhaps be methods of Board if we imagine Board as Game
-fn game_is_over(board: &Board, last_move: (i8, i8), player: &char) -> String {
-    // Check draw
-    if board
-        .matrix
-        .iter()
-        .all(|row| row.iter().all(|square| *square != None))
-    {
-        return String::from("DRAW");
-    }
-
-    // TODO: Look into scalability
-    // Check win
-    let mut count = 0;
-    let row: usize = last_move.0 as usize;
-    let col: usize = last_move.1 as usize;
-
-    for i in 0..3 {
-        if board.matrix[row][i] == Some(*player) {
-            count += 1;
-            if count == 3 {
-                return String::from("YES");
-            }
+/// Steps through game `number` (1-based) of a saved session, one move per
+/// line read from stdin, or the whole game at once once stdin is closed.
+pub fn replay(path: &str, number: usize) -> bool {
+    let records = match load_session(path) {
+        Ok(records) => records,
+        Err(message) => {
+            eprintln!("{}", message);
+            return false;
         }
-    }
-    count = 0;
-
-    for j in 0..3 {
-        if board.matrix[j][col] == Some(*player) {
-            count += 1;
-            if count == 3 {
-                return String::from("YES");
-            }
+    };
+    let record = match number.checked_sub(1).and_then(|i| records.get(i)) {
+        Some(record) => record,
+        None => {
+            eprintln!(
+                "{}: no game {}, the file has {}",
+                path,
+                number,
+                records.len()
+            );
+            return false;
         }
-    }
-    count = 0;
+    };
 
-    let mut k = 0;
-    while k < 3 {
-        if board.matrix[k][k] == Some(*player) {
-            count += 1;
-            if count == 3 {
-                return String::from("YES");
+    let rules = record.game.rules();
+    println!(
+        "Game {} of {}, {}: {} (X) vs {} (O)",
+        number,
+        records.len(),
+        record.date,
+        record.x,
+        record.o
+    );
+    println!("Press Enter for the next move, q to stop");
+    let mut game = Game::with_rules(rules, record.game.first());
+    println!("{}", game);
+
+    let mut waiting = true;
+    for (ply, &square) in record.game.history().iter().enumerate() {
+        if waiting {
+            let mut input = String::new();
+            match io::stdin().read_line(&mut input) {
+                Ok(0) | Err(_) => waiting = false,
+                Ok(_) if input.trim() == "q" => return true,
+                Ok(_) => (),
             }
         }
-        k += 1;
+        let player = game.to_move();
+        game.apply(square).expect("loaded games are legal");
+        println!("\nMove {}: {} plays {}", ply + 1, player, square);
+        println!("{}", game);
     }
 
-    if (board.matrix[0][2] == Some(*player))
-        && (board.matrix[1][1] == Some(*player))
-        && (board.matrix[2][0] == Some(*player))
-    {
-        return String::from("YES");
+    match game.outcome() {
+        Outcome::Win(player) => println!("Winner: {} ({})", player, record.name(player)),
+        Outcome::Draw => println!("Match is a draw"),
+        Outcome::Ongoing => println!("The game was not finished"),
     }
-    String::from("NO")
+    true
 }
 
-fn end_game(player: &char, board: &mut Board) {
-    match player {
-        'X' => {
-            board.score.0 += 1;
-            println!("Winner: {}", player);
-        }
-        'O' => {
-            board.score.1 += 1;
-            println!("Winner: {}", player);
-        }
-        'D' => println!("Match is a draw"),
-        _ => (),
+fn end_game(outcome: Outcome) {
+    match outcome {
+        Outcome::Win(player) => println!("Winner: {}", player),
+        Outcome::Draw => println!("Match is a draw"),
+        Outcome::Ongoing => (),
     }
-    board.matrix = [[None; 3]; 3];
-    board.round = 0;
 }
@@ -0,0 +1,109 @@
+use super::ai::Ai;
+use super::game::{Game, Move, Outcome};
+use super::input::parse_move;
+use std::io::{self, BufRead};
+
+/// Anything that can pick moves, a person or a strategy. Called `Agent`
+/// since `Player` is a side of the board.
+pub trait Agent {
+    /// Shown in results and rating tables.
+    fn name(&self) -> String;
+
+    /// The move for the side to move in `game`, `None` to resign.
+    fn choose(&mut self, game: &Game) -> Option<Move>;
+
+    /// Called with the final position and the result once a game is over,
+    /// which is a win for the other side if one resigned.
+    fn game_over(&mut self, _game: &Game, _outcome: Outcome) {}
+}
+
+impl Agent for Ai {
+    fn name(&self) -> String {
+        self.difficulty().to_string()
+    }
+
+    fn choose(&mut self, game: &Game) -> Option<Move> {
+        Ai::choose(self, game)
+    }
+}
+
+/// A person typing squares on stdin.
+pub struct Human {
+    name: String,
+}
+
+impl Human {
+    pub fn new(name: &str) -> Human {
+        Human {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl Agent for Human {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Asks until a legal square is typed in, resigns once stdin is closed.
+    fn choose(&mut self, game: &Game) -> Option<Move> {
+        println!("\n{}", game);
+        println!("{}, you play {}", self.name, game.to_move());
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            let input = lines.next()?.ok()?;
+            // Tried on a copy, the tournament plays the move itself
+            let result = parse_move(&input, game.rules())
+                .map_err(|e| e.to_string())
+                .and_then(|square| match game.clone().apply(square) {
+                    Ok(_) => Ok(square),
+                    Err(e) => Err(e.to_string()),
+                });
+            match result {
+                Ok(square) => return Some(square),
+                Err(message) => println!("{}", message),
+            }
+        }
+    }
+
+    fn game_over(&mut self, game: &Game, outcome: Outcome) {
+        println!("\n{}", game);
+        match outcome {
+            Outcome::Win(player) => println!("Winner: {}", player),
+            _ => println!("Match is a draw"),
+        }
+    }
+}
+
+/// Plays a fixed list of squares, skipping those already taken, then the
+/// first free square once the list runs out. Handy as a predictable
+/// opponent.
+pub struct Scripted {
+    name: String,
+    moves: Vec<Move>,
+}
+
+impl Scripted {
+    pub fn new(name: &str, moves: Vec<Move>) -> Scripted {
+        Scripted {
+            name: name.to_string(),
+            moves,
+        }
+    }
+}
+
+impl Agent for Scripted {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn choose(&mut self, game: &Game) -> Option<Move> {
+        let legal = game.legal_moves();
+        self.moves
+            .iter()
+            .copied()
+            .find(|square| legal.contains(square))
+            .or_else(|| legal.first().copied())
+    }
+}
@@ -0,0 +1,365 @@
+use super::game::{Game, Move, Outcome, Player, Rules};
+use super::mcts::{Budget, Mcts};
+use super::rng::Rng;
+use std::collections::HashMap;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+/// Larger boards take too long to search to the end.
+pub const PERFECT_MAX_SQUARES: usize = 16;
+
+const INFINITY: i32 = i32::MAX / 2;
+
+/// Positions the transposition table holds before it is emptied, a few
+/// tens of megabytes.
+const TABLE_LIMIT: usize = 1 << 20;
+
+/// How hard the computer tries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    /// Any empty square.
+    Random,
+    /// Wins when it can, otherwise avoids moves that let the opponent win
+    /// right away.
+    Greedy,
+    /// Minimax over the whole game tree, never loses a game it can hold.
+    Perfect,
+    /// Monte Carlo tree search, for boards too large to search to the end.
+    Mcts,
+}
+
+impl Difficulty {
+    pub fn parse(name: &str) -> Option<Difficulty> {
+        match name {
+            "random" => Some(Difficulty::Random),
+            "greedy" => Some(Difficulty::Greedy),
+            "perfect" => Some(Difficulty::Perfect),
+            "mcts" => Some(Difficulty::Mcts),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Difficulty::Random => "random",
+            Difficulty::Greedy => "greedy",
+            Difficulty::Perfect => "perfect",
+            Difficulty::Mcts => "mcts",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What a searched position is known to be worth.
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    Exact,
+    /// The search was cut off, the value is at least this.
+    Lower,
+    /// No move reached alpha, the value is at most this.
+    Upper,
+}
+
+/// Random keys for Zobrist hashing: a position's hash is the xor of the
+/// keys of its occupied squares, so a move updates it with a single xor.
+#[derive(Debug, Clone)]
+struct Zobrist {
+    rules: Rules,
+    squares: Vec<[u64; 2]>,
+    o_to_move: u64,
+}
+
+impl Zobrist {
+    fn new(rules: Rules, rng: &mut Rng) -> Zobrist {
+        Zobrist {
+            rules,
+            squares: (0..rules.squares())
+                .map(|_| [rng.next_u64(), rng.next_u64()])
+                .collect(),
+            o_to_move: rng.next_u64(),
+        }
+    }
+
+    fn square(&self, square: Move, player: Player) -> u64 {
+        let keys = self.squares[square.row * self.rules.width + square.col];
+        match player {
+            Player::X => keys[0],
+            Player::O => keys[1],
+        }
+    }
+
+    /// Hash of the stones on the board, without the side to move.
+    fn board(&self, game: &Game) -> u64 {
+        let mut hash = 0;
+        for row in 0..self.rules.height {
+            for col in 0..self.rules.width {
+                let square = Move::new(row, col);
+                if let Some(player) = game.get(square) {
+                    hash ^= self.square(square, player);
+                }
+            }
+        }
+        hash
+    }
+
+    /// Who moves next is not fixed by the stones alone when either side may
+    /// start, so it is part of the key.
+    fn key(&self, board: u64, to_move: Player) -> u64 {
+        match to_move {
+            Player::X => board,
+            Player::O => board ^ self.o_to_move,
+        }
+    }
+}
+
+/// A computer opponent.
+#[derive(Debug, Clone)]
+pub struct Ai {
+    difficulty: Difficulty,
+    rng: Rng,
+    zobrist: Option<Zobrist>,
+    /// Searched positions by hash, kept between moves and games until it
+    /// reaches `TABLE_LIMIT`.
+    table: HashMap<u64, (i32, Bound)>,
+    budget: Budget,
+    threads: usize,
+}
+
+impl Ai {
+    pub fn new(difficulty: Difficulty) -> Ai {
+        Ai::with_rng(difficulty, Rng::from_time())
+    }
+
+    /// An opponent whose choices between equal moves are reproducible.
+    pub fn with_seed(difficulty: Difficulty, seed: u64) -> Ai {
+        Ai::with_rng(difficulty, Rng::new(seed))
+    }
+
+    fn with_rng(difficulty: Difficulty, rng: Rng) -> Ai {
+        Ai {
+            difficulty,
+            rng,
+            zobrist: None,
+            table: HashMap::new(),
+            budget: Budget::Time(Duration::from_secs(1)),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// How long `Difficulty::Mcts` searches, one second unless changed.
+    pub fn with_budget(mut self, budget: Budget) -> Ai {
+        self.budget = budget;
+        self
+    }
+
+    /// How many trees `Difficulty::Mcts` grows at once, one per core unless
+    /// changed.
+    pub fn with_threads(mut self, threads: usize) -> Ai {
+        self.threads = threads;
+        self
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// The move to play for whoever is to move, `None` once the game is over.
+    pub fn choose(&mut self, game: &Game) -> Option<Move> {
+        match self.difficulty {
+            Difficulty::Random => self.rng.choose(&game.legal_moves()),
+            Difficulty::Greedy => self.greedy(game),
+            Difficulty::Perfect => self.perfect(game),
+            Difficulty::Mcts => {
+                Mcts::new(self.budget, self.threads, self.rng.next_u64()).search(game)
+            }
+        }
+    }
+
+    /// How the game ends if both sides play perfectly from here.
+    pub fn solve(&mut self, game: &Game) -> Outcome {
+        let to_move = game.to_move();
+        let board = self.zobrist_for(game.rules()).board(game);
+        match self.negamax(&mut game.clone(), board, -INFINITY, INFINITY) {
+            0 => Outcome::Draw,
+            value if value > 0 => Outcome::Win(to_move),
+            _ => Outcome::Win(to_move.other()),
+        }
+    }
+
+    fn greedy(&mut self, game: &Game) -> Option<Move> {
+        let mut game = game.clone();
+        let mut safe = Vec::new();
+        let moves = game.legal_moves();
+        for &square in &moves {
+            if let Ok(Outcome::Win(_)) = game.apply(square) {
+                return Some(square);
+            }
+            let reply_wins = game
+                .legal_moves()
+                .into_iter()
+                .any(|reply| wins(&mut game, reply));
+            if !reply_wins {
+                safe.push(square);
+            }
+            game.undo();
+        }
+        self.rng.choose(&safe).or_else(|| self.rng.choose(&moves))
+    }
+
+    fn perfect(&mut self, game: &Game) -> Option<Move> {
+        let mut game = game.clone();
+        let board = self.zobrist_for(game.rules()).board(&game);
+        let player = game.to_move();
+        let mut best = -INFINITY;
+        let mut best_moves = Vec::new();
+
+        for square in game.legal_moves() {
+            game.apply(square).ok()?;
+            let key = self.zobrist.as_ref()?.square(square, player);
+            // Searching against `best - 1` rather than `best` gives the
+            // exact value of moves that tie, so one can be picked at random
+            let value = -self.negamax(&mut game, board ^ key, -INFINITY, 1 - best);
+            game.undo();
+
+            if value > best {
+                best = value;
+                best_moves.clear();
+            }
+            if value == best {
+                best_moves.push(square);
+            }
+        }
+        self.rng.choose(&best_moves)
+    }
+
+    /// The keys for `rules`, a new table is started when the rules change.
+    fn zobrist_for(&mut self, rules: Rules) -> &Zobrist {
+        if self.zobrist.as_ref().map(|z| z.rules) != Some(rules) {
+            self.zobrist = Some(Zobrist::new(rules, &mut self.rng));
+            self.table.clear();
+        }
+        self.zobrist.as_ref().unwrap()
+    }
+
+    /// Value of the position for the player to move: positive for a win,
+    /// higher the sooner it comes, negative for a loss and 0 for a draw.
+    fn negamax(&mut self, game: &mut Game, board: u64, mut alpha: i32, mut beta: i32) -> i32 {
+        match game.outcome() {
+            // Counted from the start of the game rather than from the
+            // searched position, so stored values hold wherever they are
+            // looked up from
+            Outcome::Win(_) => {
+                return game.history().len() as i32 - game.rules().squares() as i32 - 1
+            }
+            Outcome::Draw => return 0,
+            Outcome::Ongoing => (),
+        }
+
+        let player = game.to_move();
+        let zobrist = self.zobrist.as_ref().unwrap();
+        let key = zobrist.key(board, player);
+        let moves: Vec<(Move, u64)> = game
+            .legal_moves()
+            .into_iter()
+            .map(|square| (square, zobrist.square(square, player)))
+            .collect();
+
+        if let Some(&(value, bound)) = self.table.get(&key) {
+            match bound {
+                Bound::Exact => return value,
+                Bound::Lower => alpha = alpha.max(value),
+                Bound::Upper => beta = beta.min(value),
+            }
+            if alpha >= beta {
+                return value;
+            }
+        }
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        for (square, square_key) in moves {
+            game.apply(square).expect("legal moves can be played");
+            let value = -self.negamax(game, board ^ square_key, -beta, -alpha);
+            game.undo();
+
+            best = best.max(value);
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        // Starting over is cheap next to the search that refills it
+        if self.table.len() >= TABLE_LIMIT {
+            self.table.clear();
+        }
+        self.table.insert(key, (best, bound));
+        best
+    }
+}
+
+/// Whether playing `square` wins on the spot, leaving `game` unchanged.
+fn wins(game: &mut Game, square: Move) -> bool {
+    match game.apply(square) {
+        Ok(outcome) => {
+            game.undo();
+            matches!(outcome, Outcome::Win(_))
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_empty_board_is_a_draw() {
+        let mut ai = Ai::with_seed(Difficulty::Perfect, 1);
+        assert_eq!(ai.solve(&Game::new()), Outcome::Draw);
+        assert!(ai.table.len() <= TABLE_LIMIT);
+    }
+
+    /// Plays every reply the opponent has to `perfect`, panics on a loss.
+    /// Returns the number of games played to the end.
+    fn never_loses(perfect: &mut Ai, side: Player, game: &mut Game) -> usize {
+        match game.outcome() {
+            Outcome::Ongoing => (),
+            outcome => {
+                assert_ne!(outcome, Outcome::Win(side.other()), "\n{}", game);
+                return 1;
+            }
+        }
+        let moves = if game.to_move() == side {
+            vec![perfect.choose(game).unwrap()]
+        } else {
+            game.legal_moves()
+        };
+        let mut games = 0;
+        for square in moves {
+            game.apply(square).unwrap();
+            games += never_loses(perfect, side, game);
+            game.undo();
+        }
+        games
+    }
+
+    #[test]
+    fn perfect_play_never_loses() {
+        let mut perfect = Ai::with_seed(Difficulty::Perfect, 1);
+        for side in [Player::X, Player::O] {
+            let mut game = Game::with_rules(Rules::tic_tac_toe(), Player::X);
+            assert!(never_loses(&mut perfect, side, &mut game) > 0);
+        }
+    }
+}
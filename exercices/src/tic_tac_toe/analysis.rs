@@ -0,0 +1,450 @@
+use super::game::{Game, Move, Outcome, Player, Rules};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Boards with more squares have too many positions to keep in memory.
+pub const ANALYSIS_MAX_SQUARES: usize = 12;
+
+/// Games or positions split by how they end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub x_wins: u64,
+    pub o_wins: u64,
+    pub draws: u64,
+}
+
+impl Counts {
+    fn of(outcome: Outcome, n: u64) -> Counts {
+        let mut counts = Counts::default();
+        match outcome {
+            Outcome::Win(Player::X) => counts.x_wins = n,
+            Outcome::Win(Player::O) => counts.o_wins = n,
+            Outcome::Draw => counts.draws = n,
+            Outcome::Ongoing => (),
+        }
+        counts
+    }
+
+    fn add(&mut self, other: Counts) {
+        self.x_wins += other.x_wins;
+        self.o_wins += other.o_wins;
+        self.draws += other.draws;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.x_wins + self.o_wins + self.draws
+    }
+}
+
+/// Reachable positions after a number of moves.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DepthStats {
+    pub positions: u64,
+    /// Positions left once symmetric ones are counted once.
+    pub canonical: u64,
+    /// Positions where the game is over.
+    pub finished: Counts,
+}
+
+/// Everything that can follow one opening move.
+#[derive(Debug, Clone, Copy)]
+pub struct FirstMove {
+    pub square: Move,
+    /// The result with perfect play from here.
+    pub value: Outcome,
+    /// Every way the game can go on, by result.
+    pub games: Counts,
+}
+
+/// A position up to symmetry, with its value and the moves that keep it.
+#[derive(Debug, Clone)]
+pub struct BookEntry {
+    pub depth: usize,
+    /// Rows split by `/`, squares `.`, `X` or `O`.
+    pub board: String,
+    pub to_move: Player,
+    pub value: Outcome,
+    pub best: Vec<Move>,
+}
+
+/// The complete game tree of a small board, X moving first.
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub rules: Rules,
+    /// Indexed by the number of moves played.
+    pub depths: Vec<DepthStats>,
+    pub first_moves: Vec<FirstMove>,
+    /// Every position up to symmetry, in order of depth.
+    pub book: Vec<BookEntry>,
+}
+
+impl Analysis {
+    /// `None` if the board has more than `ANALYSIS_MAX_SQUARES` squares.
+    pub fn new(rules: Rules) -> Option<Analysis> {
+        if rules.squares() > ANALYSIS_MAX_SQUARES {
+            return None;
+        }
+        let mut explorer = Explorer {
+            symmetries: symmetries(rules),
+            entries: HashMap::new(),
+            book: Vec::new(),
+            depths: vec![DepthStats::default(); rules.squares() + 1],
+        };
+
+        let mut game = Game::with_rules(rules, Player::X);
+        explorer.visit(&mut game);
+        let mut first_moves = Vec::new();
+        for square in game.legal_moves() {
+            game.apply(square).expect("legal moves can be played");
+            let (value, games) = explorer.visit(&mut game);
+            game.undo();
+            first_moves.push(FirstMove {
+                square,
+                value,
+                games,
+            });
+        }
+
+        let mut book = explorer.book;
+        book.sort_by_key(|entry| entry.depth);
+        Some(Analysis {
+            rules,
+            depths: explorer.depths,
+            first_moves,
+            book,
+        })
+    }
+
+    /// The result with perfect play from the empty board.
+    pub fn value(&self) -> Outcome {
+        self.book[0].value
+    }
+
+    /// Every complete game, by result.
+    pub fn games(&self) -> Counts {
+        let mut total = Counts::default();
+        for first in &self.first_moves {
+            total.add(first.games);
+        }
+        total
+    }
+}
+
+/// Memoised depth first search over positions up to symmetry.
+struct Explorer {
+    /// Each symmetry as the square every square is sent to.
+    symmetries: Vec<Vec<usize>>,
+    /// Value and game counts by canonical key.
+    entries: HashMap<u64, (Outcome, Counts)>,
+    book: Vec<BookEntry>,
+    depths: Vec<DepthStats>,
+}
+
+impl Explorer {
+    fn visit(&mut self, game: &mut Game) -> (Outcome, Counts) {
+        let keys: Vec<u64> = self
+            .symmetries
+            .iter()
+            .map(|symmetry| key(game, symmetry))
+            .collect();
+        let canonical = *keys.iter().min().unwrap();
+        if let Some(&entry) = self.entries.get(&canonical) {
+            return entry;
+        }
+
+        let to_move = game.to_move();
+        let (value, games, best) = match game.outcome() {
+            Outcome::Ongoing => {
+                let mut games = Counts::default();
+                let mut children = Vec::new();
+                for square in game.legal_moves() {
+                    game.apply(square).expect("legal moves can be played");
+                    let (value, child_games) = self.visit(game);
+                    game.undo();
+                    games.add(child_games);
+                    children.push((square, value));
+                }
+                // The side to move picks a win over a draw over a loss
+                let value = [Outcome::Win(to_move), Outcome::Draw]
+                    .iter()
+                    .copied()
+                    .find(|&outcome| children.iter().any(|&(_, value)| value == outcome))
+                    .unwrap_or(Outcome::Win(to_move.other()));
+                let best = children
+                    .into_iter()
+                    .filter(|&(_, child)| child == value)
+                    .map(|(square, _)| square)
+                    .collect();
+                (value, games, best)
+            }
+            outcome => (outcome, Counts::of(outcome, 1), Vec::new()),
+        };
+
+        // Symmetric positions are all reachable, count each distinct one
+        let mut distinct = keys.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let depth = game.history().len();
+        let stats = &mut self.depths[depth];
+        stats.positions += distinct.len() as u64;
+        stats.canonical += 1;
+        stats
+            .finished
+            .add(Counts::of(game.outcome(), distinct.len() as u64));
+
+        self.entries.insert(canonical, (value, games));
+        self.book.push(BookEntry {
+            depth,
+            board: board_text(game),
+            to_move,
+            value,
+            best,
+        });
+        (value, games)
+    }
+}
+
+/// The board in base 3 with its squares moved by `symmetry`.
+fn key(game: &Game, symmetry: &[usize]) -> u64 {
+    let width = game.rules().width;
+    let mut digits = vec![0; symmetry.len()];
+    for (index, &target) in symmetry.iter().enumerate() {
+        digits[target] = match game.get(Move::new(index / width, index % width)) {
+            None => 0,
+            Some(Player::X) => 1,
+            Some(Player::O) => 2,
+        };
+    }
+    digits.iter().rev().fold(0, |key, &digit| key * 3 + digit)
+}
+
+/// Where a square at row, column ends up.
+type Transform = Box<dyn Fn(usize, usize) -> (usize, usize)>;
+
+/// Rotations and reflections that map the board onto itself: all 8 for a
+/// square board, otherwise the 4 that keep rows as rows.
+fn symmetries(rules: Rules) -> Vec<Vec<usize>> {
+    let (w, h) = (rules.width, rules.height);
+    let mut transforms: Vec<Transform> = vec![
+        Box::new(|r, c| (r, c)),
+        Box::new(move |r, c| (r, w - 1 - c)),
+        Box::new(move |r, c| (h - 1 - r, c)),
+        Box::new(move |r, c| (h - 1 - r, w - 1 - c)),
+    ];
+    if w == h {
+        let n = w;
+        transforms.push(Box::new(|r, c| (c, r)));
+        transforms.push(Box::new(move |r, c| (c, n - 1 - r)));
+        transforms.push(Box::new(move |r, c| (n - 1 - c, r)));
+        transforms.push(Box::new(move |r, c| (n - 1 - c, n - 1 - r)));
+    }
+    transforms
+        .iter()
+        .map(|transform| {
+            (0..rules.squares())
+                .map(|index| {
+                    let (r, c) = transform(index / w, index % w);
+                    r * w + c
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn board_text(game: &Game) -> String {
+    let rules = game.rules();
+    let rows: Vec<String> = (0..rules.height)
+        .map(|row| {
+            (0..rules.width)
+                .map(|col| match game.get(Move::new(row, col)) {
+                    Some(player) => player.symbol(),
+                    None => '.',
+                })
+                .collect()
+        })
+        .collect();
+    rows.join("/")
+}
+
+fn value_text(value: Outcome) -> &'static str {
+    match value {
+        Outcome::Win(Player::X) => "X wins",
+        Outcome::Win(Player::O) => "O wins",
+        Outcome::Draw | Outcome::Ongoing => "draw",
+    }
+}
+
+/// Prints positions per depth and what each opening move leads to.
+pub fn write_stats(analysis: &Analysis, out: &mut impl Write) -> io::Result<()> {
+    let rules = analysis.rules;
+    writeln!(
+        out,
+        "{}x{} board, {} in a row: {} with perfect play",
+        rules.width,
+        rules.height,
+        rules.win_length,
+        value_text(analysis.value())
+    )?;
+
+    writeln!(out, "\nPositions by depth (finished positions by result)")?;
+    writeln!(
+        out,
+        "{:>5} {:>10} {:>10} {:>8} {:>8} {:>8}",
+        "depth", "positions", "canonical", "X wins", "O wins", "draws"
+    )?;
+    let mut total = DepthStats::default();
+    for (depth, stats) in analysis.depths.iter().enumerate() {
+        writeln!(
+            out,
+            "{:>5} {:>10} {:>10} {:>8} {:>8} {:>8}",
+            depth,
+            stats.positions,
+            stats.canonical,
+            stats.finished.x_wins,
+            stats.finished.o_wins,
+            stats.finished.draws
+        )?;
+        total.positions += stats.positions;
+        total.canonical += stats.canonical;
+        total.finished.add(stats.finished);
+    }
+    writeln!(
+        out,
+        "{:>5} {:>10} {:>10} {:>8} {:>8} {:>8}",
+        "all",
+        total.positions,
+        total.canonical,
+        total.finished.x_wins,
+        total.finished.o_wins,
+        total.finished.draws
+    )?;
+
+    writeln!(out, "\nGames by first move")?;
+    writeln!(
+        out,
+        "{:>5} {:>8} {:>8} {:>8} {:>8}",
+        "move", "value", "X wins", "O wins", "draws"
+    )?;
+    for first in &analysis.first_moves {
+        writeln!(
+            out,
+            "{:>5} {:>8} {:>8} {:>8} {:>8}",
+            first.square.to_string(),
+            value_text(first.value),
+            first.games.x_wins,
+            first.games.o_wins,
+            first.games.draws
+        )?;
+    }
+    let games = analysis.games();
+    writeln!(
+        out,
+        "{:>5} {:>8} {:>8} {:>8} {:>8}",
+        "all", "", games.x_wins, games.o_wins, games.draws
+    )
+}
+
+/// Writes one line per unfinished position up to `max_depth` moves in:
+/// the board, the side to move, the result with perfect play and the
+/// moves that get it.
+pub fn write_book(analysis: &Analysis, max_depth: usize, out: &mut impl Write) -> io::Result<()> {
+    let rules = analysis.rules;
+    writeln!(
+        out,
+        "# Opening book, {}x{} board, {} in a row, positions up to symmetry",
+        rules.width, rules.height, rules.win_length
+    )?;
+    writeln!(out, "# board (rows split by /), to move, value, best moves")?;
+    for entry in &analysis.book {
+        if entry.depth > max_depth || entry.best.is_empty() {
+            continue;
+        }
+        let best: Vec<String> = entry.best.iter().map(|square| square.to_string()).collect();
+        writeln!(
+            out,
+            "{} {} {} {}",
+            entry.board,
+            entry.to_move,
+            value_text(entry.value).replace(' ', "-"),
+            best.join(" ")
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ai::{Ai, Difficulty};
+    use super::*;
+
+    /// Sets up a book position by playing its stones in board order.
+    fn position(rules: Rules, board: &str) -> Game {
+        let mut stones = (Vec::new(), Vec::new());
+        for (row, line) in board.split('/').enumerate() {
+            for (col, square) in line.chars().enumerate() {
+                match square {
+                    'X' => stones.0.push(Move::new(row, col)),
+                    'O' => stones.1.push(Move::new(row, col)),
+                    _ => (),
+                }
+            }
+        }
+        let mut game = Game::with_rules(rules, Player::X);
+        for (index, &square) in stones.0.iter().enumerate() {
+            game.apply(square).unwrap();
+            if let Some(&reply) = stones.1.get(index) {
+                game.apply(reply).unwrap();
+            }
+        }
+        game
+    }
+
+    #[test]
+    fn counts_the_whole_3x3_tree() {
+        let analysis = Analysis::new(Rules::tic_tac_toe()).unwrap();
+        let positions: u64 = analysis.depths.iter().map(|stats| stats.positions).sum();
+        let canonical: u64 = analysis.depths.iter().map(|stats| stats.canonical).sum();
+        let mut finished = Counts::default();
+        for stats in &analysis.depths {
+            finished.add(stats.finished);
+        }
+        assert_eq!(positions, 5478);
+        assert_eq!(canonical, 765);
+        assert_eq!(analysis.book.len(), 765);
+        assert_eq!(finished.total(), 958);
+        assert_eq!(
+            analysis.games(),
+            Counts {
+                x_wins: 131_184,
+                o_wins: 77_904,
+                draws: 46_080,
+            }
+        );
+        assert_eq!(analysis.games().total(), 255_168);
+        assert_eq!(analysis.value(), Outcome::Draw);
+    }
+
+    #[test]
+    fn agrees_with_the_perfect_ai() {
+        let rules = Rules::tic_tac_toe();
+        let analysis = Analysis::new(rules).unwrap();
+        let mut ai = Ai::with_seed(Difficulty::Perfect, 0);
+        let mut solved = 0;
+        for entry in &analysis.book {
+            // Finished positions may hold a line before their last stone,
+            // so only unfinished ones can be set up in board order
+            if entry.best.is_empty() {
+                assert_ne!(entry.value, Outcome::Ongoing, "{}", entry.board);
+                continue;
+            }
+            let game = position(rules, &entry.board);
+            assert_eq!(game.history().len(), entry.depth, "{}", entry.board);
+            assert_eq!(game.to_move(), entry.to_move, "{}", entry.board);
+            assert_eq!(game.outcome(), Outcome::Ongoing, "{}", entry.board);
+            assert_eq!(ai.solve(&game), entry.value, "{}", entry.board);
+            solved += 1;
+        }
+        // 765 canonical positions, 138 of them finished
+        assert_eq!(solved, 765 - 138);
+    }
+}
@@ -0,0 +1,519 @@
+use super::ultimate;
+use super::{
+    connect, parse_move, replay, run, serve, tui, write_book, write_results, write_stats, Agent,
+    Ai, Analysis, Budget, Config, Difficulty, Human, Player, Rules, Scripted, Tournament,
+    ANALYSIS_MAX_SQUARES, DEFAULT_PORT, MAX_SQUARES, MAX_THREADS, PERFECT_MAX_SQUARES,
+};
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::{self, IsTerminal, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+
+const USAGE: &str = "\
+usage: exercices ttt [OPTIONS]
+       exercices ttt replay FILE [--game N]
+       exercices ttt serve [--bind ADDR] [--port P] [--size WxH] [--win K] [--gomoku]
+       exercices ttt connect [HOST:PORT] [--name NAME]
+       exercices ttt analyse [--size WxH] [--win K] [--book FILE] [--book-depth N]
+       exercices ttt ultimate
+       exercices ttt tournament [--games N] [OPTIONS] AGENT AGENT...
+
+Plays tic-tac-toe in the terminal, one human playing both sides unless
+the computer takes one of them. `replay` steps through a saved game, one
+move each time Enter is pressed. `serve` hosts a game for two players who
+join with `connect` (default 127.0.0.1:7878). `analyse` solves every
+position of a small board and prints statistics. `ultimate` plays on
+a 3x3 grid of boards, where each move picks the opponent's next board.
+`tournament` plays every AGENT against every other and rates them. An
+AGENT is human, one of the --ai levels, or script:MOVES with squares
+split by /, e.g. script:2,2/1,1/3,3.
+
+options:
+    --size WxH          board of W columns and H rows (default 3x3)
+    --win K             K in a row wins (default 3)
+    --gomoku            shorthand for --size 15x15 --win 5
+    --ai LEVEL          play against the computer: random, greedy, perfect
+                        or mcts (tree search, for large boards)
+    --ai-plays X|O      the side the computer plays (default O)
+    --seed N            make the computer's choices reproducible
+    --budget B          mcts search per move, a time such as 500ms or 2s,
+                        or a number of playouts (default 1s)
+    --threads N         mcts search threads, at most 64 (default: one
+                        per core)
+    --tui               full-screen board played with the arrow keys
+    --load FILE         continue a saved session
+    --save FILE         save the session after every game
+    --game N            the game to replay (default 1)
+    --bind ADDR         address to serve on (default 127.0.0.1, this
+                        machine only, 0.0.0.0 for every network)
+    --port P            port to serve on (default 7878)
+    --name NAME         the name shown to the server (default $USER)
+    --book FILE         write the best moves of every position to FILE
+    --book-depth N      only positions up to N moves in (default all)
+    --games N           tournament games per pairing (default 2)";
+
+/// Entry point of `exercices ttt`, returns the process exit status.
+pub fn main(args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        Some("replay") => return replay_main(&args[1..]),
+        Some("serve") => return serve_main(&args[1..]),
+        Some("connect") => return connect_main(&args[1..]),
+        Some("analyse") | Some("analyze") => return analyse_main(&args[1..]),
+        Some("ultimate") => return ultimate_main(&args[1..]),
+        Some("tournament") => return tournament_main(&args[1..]),
+        _ => (),
+    }
+    match parse_args(args) {
+        Ok(Some(config)) => {
+            // The full-screen UI needs a terminal on both ends, the line
+            // based game works anywhere
+            let terminal = io::stdin().is_terminal() && io::stdout().is_terminal();
+            if config.tui && !terminal {
+                eprintln!("--tui needs a terminal, playing line by line");
+            }
+            let ok = if config.tui && terminal {
+                tui::run(&config)
+            } else {
+                run(&config)
+            };
+            if ok {
+                0
+            } else {
+                1
+            }
+        }
+        Ok(None) => {
+            println!("{}", USAGE);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            2
+        }
+    }
+}
+
+/// `Ok(None)` when help was asked for.
+fn parse_args(args: &[String]) -> Result<Option<Config>, String> {
+    let (mut width, mut height, mut win_length) = (3, 3, 3);
+    let mut config = Config::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match arg.as_str() {
+            "--size" => {
+                let size = value(arg)?;
+                let parsed = size
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+                match parsed {
+                    Some((w, h)) => {
+                        width = w;
+                        height = h;
+                    }
+                    None => return Err(format!("--size expects WxH, got {}", size)),
+                }
+            }
+            "--win" => {
+                win_length = value(arg)?
+                    .parse()
+                    .map_err(|_| "--win must be a number".to_string())?
+            }
+            "--gomoku" => {
+                let gomoku = Rules::gomoku();
+                width = gomoku.width;
+                height = gomoku.height;
+                win_length = gomoku.win_length;
+            }
+            "--ai" => {
+                let level = value(arg)?;
+                config.ai = Some(
+                    Difficulty::parse(&level)
+                        .ok_or_else(|| format!("unknown --ai level {}", level))?,
+                );
+            }
+            "--ai-plays" => {
+                config.ai_plays = match value(arg)?.as_str() {
+                    "X" | "x" => Player::X,
+                    "O" | "o" => Player::O,
+                    other => return Err(format!("--ai-plays expects X or O, got {}", other)),
+                }
+            }
+            "--seed" => {
+                config.seed = Some(
+                    value(arg)?
+                        .parse()
+                        .map_err(|_| "--seed must be a number".to_string())?,
+                )
+            }
+            "--budget" => {
+                let budget = value(arg)?;
+                config.budget = Some(Budget::parse(&budget).ok_or_else(|| {
+                    format!("--budget expects 500ms, 2s or N above zero, got {}", budget)
+                })?);
+            }
+            "--threads" => {
+                config.threads = match value(arg)?.parse() {
+                    Ok(threads) if (1..=MAX_THREADS).contains(&threads) => Some(threads),
+                    _ => return Err(format!("--threads must be between 1 and {}", MAX_THREADS)),
+                }
+            }
+            "--tui" => config.tui = true,
+            "--load" => config.load = Some(value(arg)?),
+            "--save" => config.save = Some(value(arg)?),
+            "-h" | "--help" => return Ok(None),
+            other => return Err(format!("unknown option {}", other)),
+        }
+    }
+
+    config.rules =
+        Rules::new(width, height, win_length).ok_or_else(|| match width.checked_mul(height) {
+            Some(squares) if squares <= MAX_SQUARES => format!(
+                "no line of {} fits on a {}x{} board",
+                win_length, width, height
+            ),
+            _ => format!("boards are limited to {} squares", MAX_SQUARES),
+        })?;
+    if config.ai == Some(Difficulty::Perfect) && config.rules.squares() > PERFECT_MAX_SQUARES {
+        return Err(format!(
+            "perfect play is limited to boards of {} squares, try --ai mcts",
+            PERFECT_MAX_SQUARES
+        ));
+    }
+    Ok(Some(config))
+}
+
+fn replay_main(args: &[String]) -> i32 {
+    let mut path = None;
+    let mut number = 1;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--game" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => number = n,
+                None => {
+                    eprintln!("--game needs a number\n\n{}", USAGE);
+                    return 2;
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return 0;
+            }
+            other if other.starts_with('-') || path.is_some() => {
+                eprintln!("unexpected argument {}\n\n{}", other, USAGE);
+                return 2;
+            }
+            other => path = Some(other.to_string()),
+        }
+    }
+
+    match path {
+        Some(path) if replay(&path, number) => 0,
+        Some(_) => 1,
+        None => {
+            eprintln!("replay needs a FILE\n\n{}", USAGE);
+            2
+        }
+    }
+}
+
+/// Whether only the board was configured, for commands without players.
+fn board_only(config: &Config) -> bool {
+    config.ai.is_none() && config.load.is_none() && config.save.is_none() && !config.tui
+}
+
+fn serve_main(args: &[String]) -> i32 {
+    // Take out the address and port, the rest are the usual board options
+    let mut bind = IpAddr::from([127, 0, 0, 1]);
+    let mut port = DEFAULT_PORT;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--port" {
+            match args.next().and_then(|p| p.parse().ok()) {
+                Some(p) => port = p,
+                None => {
+                    eprintln!("--port needs a number\n\n{}", USAGE);
+                    return 2;
+                }
+            }
+        } else if arg == "--bind" {
+            match args.next().and_then(|a| a.parse().ok()) {
+                Some(a) => bind = a,
+                None => {
+                    eprintln!("--bind needs an IP address\n\n{}", USAGE);
+                    return 2;
+                }
+            }
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+
+    let config = match parse_args(&rest) {
+        Ok(Some(config)) if board_only(&config) => config,
+        Ok(Some(_)) => {
+            eprintln!("serve only takes board options\n\n{}", USAGE);
+            return 2;
+        }
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return 2;
+        }
+    };
+
+    let result = TcpListener::bind((bind, port)).and_then(|listener| {
+        println!("Waiting for two players on {}", SocketAddr::new(bind, port));
+        serve(&listener, config.rules)
+    });
+    match result {
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn connect_main(args: &[String]) -> i32 {
+    let mut address = format!("127.0.0.1:{}", DEFAULT_PORT);
+    let mut name = env::var("USER").unwrap_or_else(|_| "player".to_string());
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--name" => match args.next() {
+                Some(n) => name = n.clone(),
+                None => {
+                    eprintln!("--name needs a value\n\n{}", USAGE);
+                    return 2;
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return 0;
+            }
+            other if other.starts_with('-') => {
+                eprintln!("unknown option {}\n\n{}", other, USAGE);
+                return 2;
+            }
+            other => address = other.to_string(),
+        }
+    }
+
+    let stdin = io::stdin();
+    let result =
+        TcpStream::connect(&address).and_then(|stream| connect(stream, &name, stdin.lock()));
+    match result {
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("{}: {}", address, err);
+            1
+        }
+    }
+}
+
+fn analyse_main(args: &[String]) -> i32 {
+    let mut book = None;
+    let mut book_depth = usize::MAX;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--book" => match args.next() {
+                Some(path) => book = Some(path.clone()),
+                None => {
+                    eprintln!("--book needs a value\n\n{}", USAGE);
+                    return 2;
+                }
+            },
+            "--book-depth" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => book_depth = n,
+                None => {
+                    eprintln!("--book-depth needs a number\n\n{}", USAGE);
+                    return 2;
+                }
+            },
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let rules = match parse_args(&rest) {
+        Ok(Some(config)) if board_only(&config) => config.rules,
+        Ok(Some(_)) => {
+            eprintln!("analyse only takes board options\n\n{}", USAGE);
+            return 2;
+        }
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return 2;
+        }
+    };
+    let analysis = match Analysis::new(rules) {
+        Some(analysis) => analysis,
+        None => {
+            eprintln!(
+                "analysis is limited to boards of {} squares",
+                ANALYSIS_MAX_SQUARES
+            );
+            return 2;
+        }
+    };
+
+    let stdout = io::stdout();
+    let mut result = write_stats(&analysis, &mut stdout.lock());
+    if let Some(path) = &book {
+        result = result.and_then(|()| {
+            let mut out = BufWriter::new(File::create(path)?);
+            write_book(&analysis, book_depth, &mut out)?;
+            out.flush()
+        });
+    }
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn ultimate_main(args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        None => {
+            ultimate::run();
+            0
+        }
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            0
+        }
+        Some(other) => {
+            eprintln!("ultimate takes no options, got {}\n\n{}", other, USAGE);
+            2
+        }
+    }
+}
+
+fn tournament_main(args: &[String]) -> i32 {
+    // Agents are the bare arguments, the rest are options
+    let mut games = 2;
+    let mut specs = Vec::new();
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) if n > 0 => games = n,
+                _ => {
+                    eprintln!("--games needs a positive number\n\n{}", USAGE);
+                    return 2;
+                }
+            },
+            "--size" | "--win" | "--seed" | "--budget" | "--threads" => {
+                rest.push(arg.clone());
+                rest.extend(args.next().cloned());
+            }
+            "--gomoku" | "-h" | "--help" => rest.push(arg.clone()),
+            other if other.starts_with('-') => {
+                eprintln!("unknown option {}\n\n{}", other, USAGE);
+                return 2;
+            }
+            spec => specs.push(spec.to_string()),
+        }
+    }
+
+    let config = match parse_args(&rest) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return 2;
+        }
+    };
+    if specs.len() < 2 {
+        eprintln!("a tournament needs at least two agents\n\n{}", USAGE);
+        return 2;
+    }
+    let mut agents = Vec::new();
+    for (index, spec) in specs.iter().enumerate() {
+        match agent(spec, index, &config) {
+            Ok(agent) => agents.push(agent),
+            Err(message) => {
+                eprintln!("{}\n\n{}", message, USAGE);
+                return 2;
+            }
+        }
+    }
+
+    let tournament = Tournament::new(config.rules, games);
+    let (pairings, mut standings) = tournament.play(&mut agents);
+    // Agents of the same kind are told apart by their place
+    let names: Vec<String> = standings.iter().map(|s| s.name.clone()).collect();
+    for (index, standing) in standings.iter_mut().enumerate() {
+        if names.iter().filter(|&name| *name == standing.name).count() > 1 {
+            standing.name = format!("{} #{}", standing.name, index + 1);
+        }
+    }
+    let stdout = io::stdout();
+    match write_results(&pairings, &standings, &mut stdout.lock()) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+/// The agent `spec` names, the `index`th of the tournament.
+fn agent(spec: &str, index: usize, config: &Config) -> Result<Box<dyn Agent>, String> {
+    if spec == "human" {
+        return Ok(Box::new(Human::new(spec)));
+    }
+    if let Some(moves) = spec.strip_prefix("script:") {
+        let moves = moves
+            .split('/')
+            .map(|square| parse_move(square, config.rules))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("cannot read the squares of {}", spec))?;
+        return Ok(Box::new(Scripted::new("script", moves)));
+    }
+
+    let difficulty = Difficulty::parse(spec).ok_or_else(|| format!("unknown agent {}", spec))?;
+    if difficulty == Difficulty::Perfect && config.rules.squares() > PERFECT_MAX_SQUARES {
+        return Err(format!(
+            "perfect play is limited to boards of {} squares, try mcts",
+            PERFECT_MAX_SQUARES
+        ));
+    }
+    let mut ai = match config.seed {
+        Some(seed) => Ai::with_seed(difficulty, seed.wrapping_add(index as u64)),
+        None => Ai::new(difficulty),
+    };
+    if let Some(budget) = config.budget {
+        ai = ai.with_budget(budget);
+    }
+    if let Some(threads) = config.threads {
+        ai = ai.with_threads(threads);
+    }
+    Ok(Box::new(ai))
+}
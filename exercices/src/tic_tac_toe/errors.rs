@@ -1,31 +1,129 @@
+use super::game::Move;
 use std::error::Error;
 use std::fmt;
 
-#[derive(Debug)]
-pub struct MyErr {
-    details: String,
+/// Why a typed move could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputError {
+    Empty,
+    /// More than a row and a column.
+    TooManyValues,
+    InvalidRow(String),
+    InvalidColumn(String),
+    /// A single digit on a board the keypad does not map onto.
+    KeypadNeeds3x3,
+    /// A lone square in ultimate tic-tac-toe when any board may be played.
+    BoardNeeded,
+    Unrecognized(String),
 }
 
-impl MyErr {
-    pub fn new(msg: &str) -> MyErr {
-        MyErr {
-            details: String::from(msg),
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Empty => write!(f, "Enter a square, e.g. 1,3"),
+            InputError::TooManyValues => write!(f, "Error: Too many inputs\nPlease try again"),
+            InputError::InvalidRow(text) => {
+                write!(f, "Error: {:?} is not a valid row\nPlease try again", text)
+            }
+            InputError::InvalidColumn(text) => {
+                write!(
+                    f,
+                    "Error: {:?} is not a valid column\nPlease try again",
+                    text
+                )
+            }
+            InputError::KeypadNeeds3x3 => {
+                write!(f, "Keypad digits only work on a 3x3 board, enter row,col")
+            }
+            InputError::BoardNeeded => write!(
+                f,
+                "Any board is open, enter the board then the square, e.g. 5 9"
+            ),
+            InputError::Unrecognized(text) => write!(
+                f,
+                "Error: could not read {:?} as a square, try 1,3 or a3\nPlease try again",
+                text
+            ),
         }
     }
 }
 
-impl fmt::Display for MyErr {
+impl Error for InputError {}
+
+/// Why `Game::apply` refused a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    OutOfRange(Move),
+    Occupied(Move),
+    GameOver,
+    /// Ultimate tic-tac-toe only allows the board numbered here, as on a
+    /// keypad.
+    WrongBoard(usize),
+    /// An ultimate tic-tac-toe board that is already won or full.
+    BoardDecided(usize),
+}
+
+impl fmt::Display for MoveError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+        match self {
+            MoveError::OutOfRange(_) => {
+                write!(f, "Entered square is out of range\nPlease try again")
+            }
+            MoveError::Occupied(square) => write!(f, "Square {} already occupied dude...", square),
+            MoveError::GameOver => write!(f, "The game is already over"),
+            MoveError::WrongBoard(board) => {
+                write!(f, "This move must go in board {}\nPlease try again", board)
+            }
+            MoveError::BoardDecided(board) => {
+                write!(f, "Board {} is already decided\nPlease try again", board)
+            }
+        }
     }
 }
 
-impl Error for MyErr {
-    fn description(&self) -> &str {
-        &self.details
-    }
+impl Error for MoveError {}
 
-    fn cause(&self) -> Option<&Error> {
-        None //Generic error, underlying cause isn't tracked
+/// Why a saved session could not be read back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordError {
+    /// A header, square or value that does not parse.
+    Syntax { line: usize, text: String },
+    /// The size and win length do not make a board, or the board is
+    /// larger than `MAX_SQUARES`.
+    InvalidRules,
+    IllegalMove {
+        line: usize,
+        ply: usize,
+        error: MoveError,
+    },
+    /// The result stated differs from how the moves end.
+    ResultMismatch { line: usize, recorded: String },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::Syntax { line, text } => {
+                write!(f, "line {}: cannot read {:?}", line, text)
+            }
+            RecordError::InvalidRules => {
+                write!(f, "the board is too large or the win length does not fit")
+            }
+            RecordError::IllegalMove { line, ply, error } => {
+                // Move errors are worded for players, keep the first line
+                let reason = error.to_string();
+                let reason = reason.lines().next().unwrap_or_default();
+                write!(f, "line {}: move {}: {}", line, ply, reason)
+            }
+            RecordError::ResultMismatch { line, recorded } => {
+                write!(
+                    f,
+                    "line {}: result {} does not match the moves",
+                    line, recorded
+                )
+            }
+        }
     }
 }
+
+impl Error for RecordError {}
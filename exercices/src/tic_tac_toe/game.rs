@@ -0,0 +1,508 @@
+use super::errors::MoveError;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    X,
+    O,
+}
+
+impl Player {
+    pub fn other(self) -> Player {
+        match self {
+            Player::X => Player::O,
+            Player::O => Player::X,
+        }
+    }
+
+    pub fn symbol(self) -> char {
+        match self {
+            Player::X => 'X',
+            Player::O => 'O',
+        }
+    }
+}
+
+impl Display for Player {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+/// A square on the board, 0-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Move {
+    pub fn new(row: usize, col: usize) -> Move {
+        Move { row, col }
+    }
+}
+
+impl Display for Move {
+    /// Shown 1-based, the way players type squares in.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.row + 1, self.col + 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win(Player),
+    Draw,
+    Ongoing,
+}
+
+/// Largest board `Rules::new` accepts, 256x256 or any other shape of as
+/// many squares.
+pub const MAX_SQUARES: usize = 1 << 16;
+
+/// Board dimensions and how many in a row win, the m,n,k of an m,n,k-game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rules {
+    pub width: usize,
+    pub height: usize,
+    pub win_length: usize,
+}
+
+impl Rules {
+    /// `None` unless the board has squares, no more than `MAX_SQUARES`, and
+    /// a line of `win_length` fits.
+    pub fn new(width: usize, height: usize, win_length: usize) -> Option<Rules> {
+        let fits = win_length <= width.max(height);
+        let squares = width.checked_mul(height).filter(|&n| n <= MAX_SQUARES);
+        if squares.is_none() || width == 0 || height == 0 || win_length == 0 || !fits {
+            return None;
+        }
+        Some(Rules {
+            width,
+            height,
+            win_length,
+        })
+    }
+
+    /// The classic 3x3 board, three in a row.
+    pub fn tic_tac_toe() -> Rules {
+        Rules::new(3, 3, 3).unwrap()
+    }
+
+    /// Free-style Gomoku, five in a row on 15x15.
+    pub fn gomoku() -> Rules {
+        Rules::new(15, 15, 5).unwrap()
+    }
+
+    /// Cannot overflow for rules made by `new`.
+    pub fn squares(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn contains(&self, square: Move) -> bool {
+        square.row < self.height && square.col < self.width
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::tic_tac_toe()
+    }
+}
+
+/// The rules of a single game, free of any input or output.
+#[derive(Debug, Clone)]
+pub struct Game {
+    rules: Rules,
+    /// Squares row by row.
+    squares: Vec<Option<Player>>,
+    first: Player,
+    history: Vec<Move>,
+    outcome: Outcome,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game::starting_with(Player::X)
+    }
+
+    pub fn starting_with(first: Player) -> Game {
+        Game::with_rules(Rules::tic_tac_toe(), first)
+    }
+
+    pub fn with_rules(rules: Rules, first: Player) -> Game {
+        Game {
+            rules,
+            squares: vec![None; rules.squares()],
+            first,
+            history: Vec::new(),
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    pub fn get(&self, square: Move) -> Option<Player> {
+        self.squares[square.row * self.rules.width + square.col]
+    }
+
+    fn set(&mut self, square: Move, value: Option<Player>) {
+        self.squares[square.row * self.rules.width + square.col] = value;
+    }
+
+    /// The player who made the first move.
+    pub fn first(&self) -> Player {
+        self.first
+    }
+
+    /// The player whose turn it is.
+    pub fn to_move(&self) -> Player {
+        if self.history.len().is_multiple_of(2) {
+            self.first
+        } else {
+            self.first.other()
+        }
+    }
+
+    /// Moves played so far, oldest first.
+    pub fn history(&self) -> &[Move] {
+        &self.history
+    }
+
+    pub fn last_move(&self) -> Option<Move> {
+        self.history.last().copied()
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        match self.outcome {
+            Outcome::Win(player) => Some(player),
+            _ => None,
+        }
+    }
+
+    /// Every empty square, none once the game is over.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.outcome != Outcome::Ongoing {
+            return Vec::new();
+        }
+        let width = self.rules.width;
+        (0..self.rules.height)
+            .flat_map(|row| (0..width).map(move |col| Move::new(row, col)))
+            .filter(|&square| self.get(square).is_none())
+            .collect()
+    }
+
+    /// Plays `square` for the player to move.
+    pub fn apply(&mut self, square: Move) -> Result<Outcome, MoveError> {
+        self.place(square, self.to_move())
+    }
+
+    /// Plays `square` for `player` whoever's turn it is, for boards that
+    /// are not played in turn such as those of `Ultimate`.
+    pub fn place(&mut self, square: Move, player: Player) -> Result<Outcome, MoveError> {
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+        if !self.rules.contains(square) {
+            return Err(MoveError::OutOfRange(square));
+        }
+        if self.get(square).is_some() {
+            return Err(MoveError::Occupied(square));
+        }
+
+        self.set(square, Some(player));
+        self.history.push(square);
+        self.outcome = if self.winning_line(square).is_some() {
+            // Checked before a full board, so completing a line on the last
+            // square counts as a win
+            Outcome::Win(player)
+        } else if self.history.len() == self.rules.squares() {
+            Outcome::Draw
+        } else {
+            Outcome::Ongoing
+        };
+        Ok(self.outcome)
+    }
+
+    /// Takes back the last move, returning it.
+    pub fn undo(&mut self) -> Option<Move> {
+        let square = self.history.pop()?;
+        self.set(square, None);
+        self.outcome = Outcome::Ongoing;
+        Some(square)
+    }
+
+    /// The squares of a line of at least `win_length` through `square`, all
+    /// owned by whoever holds `square`. Only the four lines through the
+    /// square are scanned, so the cost does not depend on the board size.
+    pub fn winning_line(&self, square: Move) -> Option<Vec<Move>> {
+        let player = self.get(square)?;
+        let k = self.rules.win_length;
+
+        for &(dr, dc) in &[(0, 1), (1, 0), (1, 1), (1, -1)] {
+            let mut line = vec![square];
+            for &sign in &[1, -1] {
+                let mut current = square;
+                while let Some(next) = self.step(current, dr * sign, dc * sign) {
+                    if self.get(next) != Some(player) {
+                        break;
+                    }
+                    line.push(next);
+                    current = next;
+                }
+            }
+            if line.len() >= k {
+                line.sort_by_key(|m| (m.row, m.col));
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// The neighbour of `square` in direction (`dr`, `dc`), if on the board.
+    fn step(&self, square: Move, dr: isize, dc: isize) -> Option<Move> {
+        let row = square.row.checked_add_signed(dr)?;
+        let col = square.col.checked_add_signed(dc)?;
+        let next = Move::new(row, col);
+        if self.rules.contains(next) {
+            Some(next)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Game {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let margin = self.rules.height.to_string().len() + 1;
+        let separator = format!("{}{}-", " ".repeat(margin), "----".repeat(self.rules.width));
+
+        let labels: String = (1..=self.rules.width)
+            .map(|col| format!("  {:<2}", col))
+            .collect();
+        writeln!(f, "{}{}", " ".repeat(margin), labels.trim_end())?;
+
+        for row in 0..self.rules.height {
+            writeln!(f, "{}", separator)?;
+            write!(f, "{:<width$}", row + 1, width = margin)?;
+            for col in 0..self.rules.width {
+                match self.get(Move::new(row, col)) {
+                    None => write!(f, "|   ")?,
+                    Some(player) => write!(f, "| {} ", player)?,
+                }
+            }
+            writeln!(f, "|")?;
+        }
+        write!(f, "{}", separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game of `rules` after playing `squares` in turn from X.
+    fn played(rules: Rules, squares: &[(usize, usize)]) -> Game {
+        let mut game = Game::with_rules(rules, Player::X);
+        for &(row, col) in squares {
+            game.apply(Move::new(row, col)).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn lists_the_empty_squares_in_board_order() {
+        let game = played(Rules::new(3, 2, 2).unwrap(), &[(0, 1), (1, 2)]);
+        let expected = [(0, 0), (0, 2), (1, 0), (1, 1)].map(|(row, col)| Move::new(row, col));
+        assert_eq!(game.legal_moves(), expected);
+        assert_eq!(game.to_move(), Player::X);
+        assert_eq!(game.get(Move::new(1, 2)), Some(Player::O));
+        assert_eq!(game.last_move(), Some(Move::new(1, 2)));
+        assert!(played(
+            Rules::tic_tac_toe(),
+            &[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]
+        )
+        .legal_moves()
+        .is_empty());
+    }
+
+    #[test]
+    fn refuses_moves_it_cannot_play() {
+        let mut game = played(Rules::tic_tac_toe(), &[(1, 1)]);
+        assert_eq!(
+            game.apply(Move::new(1, 1)),
+            Err(MoveError::Occupied(Move::new(1, 1)))
+        );
+        assert_eq!(
+            game.apply(Move::new(0, 3)),
+            Err(MoveError::OutOfRange(Move::new(0, 3)))
+        );
+        assert_eq!(
+            game.apply(Move::new(3, 0)),
+            Err(MoveError::OutOfRange(Move::new(3, 0)))
+        );
+        // Refused moves change nothing
+        assert_eq!(game.history(), [Move::new(1, 1)]);
+        assert_eq!(game.to_move(), Player::O);
+    }
+
+    #[test]
+    fn ends_on_a_line_or_a_full_board() {
+        let mut game = played(Rules::tic_tac_toe(), &[(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!(game.winner(), None);
+        assert_eq!(game.apply(Move::new(0, 2)), Ok(Outcome::Win(Player::X)));
+        assert_eq!(game.winner(), Some(Player::X));
+        assert_eq!(game.apply(Move::new(2, 2)), Err(MoveError::GameOver));
+
+        let drawn = [
+            (1, 1),
+            (0, 0),
+            (0, 1),
+            (2, 1),
+            (1, 0),
+            (1, 2),
+            (0, 2),
+            (2, 0),
+            (2, 2),
+        ];
+        let game = played(Rules::tic_tac_toe(), &drawn);
+        assert_eq!(game.outcome(), Outcome::Draw);
+        assert_eq!(game.winner(), None);
+    }
+
+    #[test]
+    fn undo_takes_back_moves_even_after_the_end() {
+        let mut game = played(
+            Rules::tic_tac_toe(),
+            &[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)],
+        );
+        assert_eq!(game.undo(), Some(Move::new(0, 2)));
+        assert_eq!(game.outcome(), Outcome::Ongoing);
+        assert_eq!(game.to_move(), Player::X);
+        assert_eq!(game.get(Move::new(0, 2)), None);
+        assert_eq!(game.apply(Move::new(1, 2)), Ok(Outcome::Ongoing));
+
+        let mut game = Game::new();
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.to_move(), Player::X);
+    }
+
+    #[test]
+    fn a_line_on_the_last_square_wins() {
+        let mut game = played(
+            Rules::tic_tac_toe(),
+            &[
+                (2, 0),
+                (0, 0),
+                (0, 2),
+                (0, 1),
+                (1, 0),
+                (1, 1),
+                (2, 1),
+                (1, 2),
+            ],
+        );
+        assert_eq!(game.apply(Move::new(2, 2)), Ok(Outcome::Win(Player::X)));
+        assert_eq!(game.history().len(), game.rules().squares());
+        assert_eq!(game.winner(), Some(Player::X));
+    }
+
+    #[test]
+    fn rejects_boards_too_large_to_count() {
+        assert_eq!(Rules::new(usize::MAX, 2, 3), None);
+        assert_eq!(Rules::new(1 << 40, 1 << 40, 3), None);
+        assert_eq!(Rules::new(MAX_SQUARES + 1, 1, 3), None);
+        assert_eq!(
+            Rules::new(256, 256, 5).map(|rules| rules.squares()),
+            Some(MAX_SQUARES)
+        );
+    }
+
+    /// Lays `rules.win_length` stones of X from `start` in direction
+    /// `(dr, dc)`, checking the line only counts once complete, whichever
+    /// stone is looked at.
+    fn check_line(rules: Rules, start: (usize, usize), (dr, dc): (isize, isize)) {
+        let mut game = Game::with_rules(rules, Player::X);
+        let squares: Vec<Move> = (0..rules.win_length as isize)
+            .map(|i| {
+                let row = start.0.checked_add_signed(i * dr).unwrap();
+                let col = start.1.checked_add_signed(i * dc).unwrap();
+                Move::new(row, col)
+            })
+            .collect();
+        // The middle stone last, so the line is found from both sides
+        let middle = squares.len() / 2;
+        for (i, &square) in squares.iter().enumerate() {
+            if i != middle {
+                assert_eq!(game.place(square, Player::X), Ok(Outcome::Ongoing));
+                assert_eq!(game.winning_line(square), None);
+            }
+        }
+        assert_eq!(
+            game.place(squares[middle], Player::X),
+            Ok(Outcome::Win(Player::X))
+        );
+
+        let mut expected = squares.clone();
+        expected.sort_by_key(|m| (m.row, m.col));
+        for &square in &squares {
+            assert_eq!(
+                game.winning_line(square),
+                Some(expected.clone()),
+                "{:?}",
+                start
+            );
+        }
+    }
+
+    #[test]
+    fn finds_lines_in_every_direction_on_gomoku() {
+        let rules = Rules::gomoku();
+        // Along the edges and corner to corner
+        check_line(rules, (0, 10), (0, 1));
+        check_line(rules, (14, 0), (0, 1));
+        check_line(rules, (10, 14), (1, 0));
+        check_line(rules, (0, 0), (1, 0));
+        check_line(rules, (10, 10), (1, 1));
+        check_line(rules, (0, 0), (1, 1));
+        check_line(rules, (0, 14), (1, -1));
+        check_line(rules, (10, 4), (1, -1));
+        // In the open
+        check_line(rules, (7, 3), (0, 1));
+        check_line(rules, (5, 7), (1, 1));
+    }
+
+    #[test]
+    fn finds_lines_on_rectangular_boards() {
+        let wide = Rules::new(7, 4, 4).unwrap();
+        check_line(wide, (3, 3), (0, 1));
+        check_line(wide, (0, 6), (1, 0));
+        check_line(wide, (0, 3), (1, 1));
+        check_line(wide, (0, 3), (1, -1));
+        let tall = Rules::new(2, 6, 3).unwrap();
+        check_line(tall, (3, 1), (1, 0));
+        check_line(tall, (0, 0), (1, 0));
+
+        // Four in a row does not win when five are needed, nor does a
+        // line broken by the other player
+        let mut game = Game::with_rules(Rules::gomoku(), Player::X);
+        for col in 0..4 {
+            game.place(Move::new(0, col), Player::X).unwrap();
+        }
+        game.place(Move::new(0, 4), Player::O).unwrap();
+        assert_eq!(game.place(Move::new(0, 5), Player::X), Ok(Outcome::Ongoing));
+        assert_eq!(game.winning_line(Move::new(0, 3)), None);
+    }
+}
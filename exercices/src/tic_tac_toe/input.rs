@@ -0,0 +1,222 @@
+use super::errors::InputError;
+use super::game::{Move, Rules};
+
+/// Reads a square the way players type it in:
+///
+/// * `1,3` or `1 3`, row then column, counted from 1
+/// * `c1`, column letter then row, like a chess square
+/// * a single digit laid out like a numeric keypad, 3x3 boards only:
+///
+/// ```text
+///     7 | 8 | 9
+///     4 | 5 | 6
+///     1 | 2 | 3
+/// ```
+///
+/// The square is not checked against the board size, `Game::apply` does
+/// that.
+pub fn parse_move(input: &str, rules: Rules) -> Result<Move, InputError> {
+    let text = input.trim();
+    if text.is_empty() {
+        return Err(InputError::Empty);
+    }
+
+    if let Some((row, col)) = text.split_once(',') {
+        if col.contains(',') {
+            return Err(InputError::TooManyValues);
+        }
+        return Ok(Move::new(row_index(row.trim())?, col_index(col.trim())?));
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    match words.as_slice() {
+        [row, col] => Ok(Move::new(row_index(row)?, col_index(col)?)),
+        [word] if word.starts_with(|c: char| c.is_ascii_alphabetic()) => algebraic(word),
+        [word] if word.len() == 1 && word.starts_with(|c: char| c.is_ascii_digit()) => {
+            keypad(word, rules)
+        }
+        [word] => Err(InputError::Unrecognized(word.to_string())),
+        _ => Err(InputError::TooManyValues),
+    }
+}
+
+fn row_index(text: &str) -> Result<usize, InputError> {
+    index(text).ok_or_else(|| InputError::InvalidRow(text.to_string()))
+}
+
+fn col_index(text: &str) -> Result<usize, InputError> {
+    index(text).ok_or_else(|| InputError::InvalidColumn(text.to_string()))
+}
+
+/// A 1-based number made 0-based.
+fn index(text: &str) -> Option<usize> {
+    text.parse::<usize>().ok()?.checked_sub(1)
+}
+
+/// `b3` is the second column of the third row.
+fn algebraic(word: &str) -> Result<Move, InputError> {
+    let (letter, row) = word.split_at(1);
+    if row.is_empty() || !row.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(InputError::Unrecognized(word.to_string()));
+    }
+    let col = letter.to_ascii_lowercase().as_bytes()[0] - b'a';
+    Ok(Move::new(row_index(row)?, col as usize))
+}
+
+fn keypad(word: &str, rules: Rules) -> Result<Move, InputError> {
+    if rules.width != 3 || rules.height != 3 {
+        return Err(InputError::KeypadNeeds3x3);
+    }
+    word.parse()
+        .ok()
+        .and_then(keypad_square)
+        .ok_or_else(|| InputError::Unrecognized(word.to_string()))
+}
+
+/// The square of a 3x3 board under keypad `digit`.
+pub(super) fn keypad_square(digit: usize) -> Option<Move> {
+    match digit {
+        1..=9 => Some(Move::new(2 - (digit - 1) / 3, (digit - 1) % 3)),
+        _ => None,
+    }
+}
+
+/// Reads a square of ultimate tic-tac-toe, counted on the whole 9x9 grid:
+///
+/// * `5 9`, `5,9` or `59`, the board then the square within it, both
+///   numbered like a keypad (see `parse_move`)
+/// * `9` alone, the square within `board` when the move must go there
+/// * `e5`, column letter then row of the whole grid
+pub fn parse_ultimate_move(input: &str, board: Option<Move>) -> Result<Move, InputError> {
+    let text = input.trim();
+    if text.is_empty() {
+        return Err(InputError::Empty);
+    }
+    if text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        if text.contains(|c: char| c == ',' || c.is_whitespace()) {
+            return Err(InputError::TooManyValues);
+        }
+        return algebraic(text);
+    }
+
+    let digits: Vec<char> = text
+        .chars()
+        .filter(|&c| c != ',' && !c.is_whitespace())
+        .collect();
+    let squares: Option<Vec<Move>> = digits
+        .iter()
+        .map(|c| c.to_digit(10).and_then(|d| keypad_square(d as usize)))
+        .collect();
+    let squares = squares.ok_or_else(|| InputError::Unrecognized(text.to_string()))?;
+    let (board, square) = match (squares.as_slice(), board) {
+        (&[board, square], _) => (board, square),
+        (&[square], Some(board)) => (board, square),
+        (&[_], None) => return Err(InputError::BoardNeeded),
+        _ => return Err(InputError::TooManyValues),
+    };
+    Ok(Move::new(
+        board.row * 3 + square.row,
+        board.col * 3 + square.col,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HUGE: &str = "99999999999999999999999";
+
+    #[test]
+    fn reads_squares() {
+        let rules = Rules::tic_tac_toe();
+        let cases = [
+            ("1,3", Move::new(0, 2)),
+            (" 1 , 3 ", Move::new(0, 2)),
+            ("1 3", Move::new(0, 2)),
+            ("a3", Move::new(2, 0)),
+            ("C1", Move::new(0, 2)),
+            ("7", Move::new(0, 0)),
+            ("8", Move::new(0, 1)),
+            ("9", Move::new(0, 2)),
+            ("4", Move::new(1, 0)),
+            ("5", Move::new(1, 1)),
+            ("6", Move::new(1, 2)),
+            ("1", Move::new(2, 0)),
+            ("2", Move::new(2, 1)),
+            ("3", Move::new(2, 2)),
+        ];
+        for (input, square) in cases {
+            assert_eq!(parse_move(input, rules), Ok(square), "{:?}", input);
+        }
+        // Left for `Game::apply` to check against the board
+        assert_eq!(parse_move("4,4", rules), Ok(Move::new(3, 3)));
+    }
+
+    #[test]
+    fn names_what_is_wrong() {
+        let rules = Rules::tic_tac_toe();
+        let huge_row = format!("{},1", HUGE);
+        let huge_col = format!("1 {}", HUGE);
+        let huge_letter = format!("a{}", HUGE);
+        let cases = [
+            ("", InputError::Empty),
+            ("   ", InputError::Empty),
+            ("1,", InputError::InvalidColumn(String::new())),
+            (",3", InputError::InvalidRow(String::new())),
+            ("1,2,3", InputError::TooManyValues),
+            ("1 2 3", InputError::TooManyValues),
+            ("0,1", InputError::InvalidRow("0".to_string())),
+            ("1 -2", InputError::InvalidColumn("-2".to_string())),
+            ("a0", InputError::InvalidRow("0".to_string())),
+            ("x", InputError::Unrecognized("x".to_string())),
+            ("b3c", InputError::Unrecognized("b3c".to_string())),
+            ("0", InputError::Unrecognized("0".to_string())),
+            ("12", InputError::Unrecognized("12".to_string())),
+            (&huge_row, InputError::InvalidRow(HUGE.to_string())),
+            (&huge_col, InputError::InvalidColumn(HUGE.to_string())),
+            (&huge_letter, InputError::InvalidRow(HUGE.to_string())),
+        ];
+        for (input, error) in cases {
+            assert_eq!(parse_move(input, rules), Err(error), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn keypad_digits_need_a_3x3_board() {
+        for rules in [Rules::gomoku(), Rules::new(3, 4, 3).unwrap()] {
+            for digit in 1..=9 {
+                let input = digit.to_string();
+                assert_eq!(parse_move(&input, rules), Err(InputError::KeypadNeeds3x3));
+            }
+            assert_eq!(parse_move("2,3", rules), Ok(Move::new(1, 2)));
+        }
+    }
+
+    #[test]
+    fn reads_ultimate_squares() {
+        let centre = Some(Move::new(1, 1));
+        let cases = [
+            ("5 9", None, Ok(Move::new(3, 5))),
+            ("5,9", None, Ok(Move::new(3, 5))),
+            ("59", None, Ok(Move::new(3, 5))),
+            ("7 1", None, Ok(Move::new(2, 0))),
+            ("9", centre, Ok(Move::new(3, 5))),
+            ("e5", None, Ok(Move::new(4, 4))),
+            ("I1", None, Ok(Move::new(0, 8))),
+            ("9", None, Err(InputError::BoardNeeded)),
+            ("", centre, Err(InputError::Empty)),
+            ("5 9 1", None, Err(InputError::TooManyValues)),
+            ("e 5", None, Err(InputError::TooManyValues)),
+            (
+                "0 5",
+                None,
+                Err(InputError::Unrecognized("0 5".to_string())),
+            ),
+            ("5x", None, Err(InputError::Unrecognized("5x".to_string()))),
+            ("e0", None, Err(InputError::InvalidRow("0".to_string()))),
+        ];
+        for (input, board, expected) in cases {
+            assert_eq!(parse_ultimate_move(input, board), expected, "{:?}", input);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use super::game::{Game, Move, Outcome, Player};
+use super::rng::Rng;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exploration constant of UCB1, the usual square root of two.
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// Most search threads, each one keeps a tree of its own.
+pub const MAX_THREADS: usize = 64;
+
+/// How long a search may run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// Playouts in total, shared between the threads. The same seed gives
+    /// the same move.
+    Iterations(u32),
+    /// Wall clock time, each thread searches until it runs out.
+    Time(Duration),
+}
+
+impl Budget {
+    /// Reads `500ms`, `2s` or a plain number of iterations, `None` for a
+    /// budget of zero which would leave no move to pick.
+    pub fn parse(text: &str) -> Option<Budget> {
+        let budget = if let Some(millis) = text.strip_suffix("ms") {
+            millis
+                .parse()
+                .ok()
+                .map(Duration::from_millis)
+                .map(Budget::Time)
+        } else if let Some(secs) = text.strip_suffix('s') {
+            secs.parse().ok().map(Duration::from_secs).map(Budget::Time)
+        } else {
+            text.parse().ok().map(Budget::Iterations)
+        };
+        budget.filter(|&budget| {
+            budget != Budget::Iterations(0) && budget != Budget::Time(Duration::ZERO)
+        })
+    }
+}
+
+/// Monte Carlo tree search settings. Each thread grows its own tree from a
+/// differently seeded generator, and the visit counts at the root are
+/// summed to pick the move.
+#[derive(Debug, Clone, Copy)]
+pub struct Mcts {
+    pub budget: Budget,
+    pub threads: usize,
+    pub seed: u64,
+}
+
+impl Mcts {
+    pub fn new(budget: Budget, threads: usize, seed: u64) -> Mcts {
+        Mcts {
+            budget,
+            threads: threads.clamp(1, MAX_THREADS),
+            seed,
+        }
+    }
+
+    /// The most visited move for whoever is to move, `None` once the game
+    /// is over.
+    pub fn search(&self, game: &Game) -> Option<Move> {
+        let moves = game.legal_moves();
+        if moves.len() <= 1 {
+            return moves.first().copied();
+        }
+
+        let mut children = vec![];
+        for i in 0..self.threads {
+            let game = game.clone();
+            let budget = match self.budget {
+                Budget::Iterations(total) => {
+                    // Spread the remainder so the total is exact
+                    let share = total / self.threads as u32;
+                    Budget::Iterations(share + ((i as u32) < total % self.threads as u32) as u32)
+                }
+                time => time,
+            };
+            let seed = self.seed.wrapping_add(i as u64);
+            children.push(thread::spawn(move || {
+                Tree::new(&game).grow(game, budget, &mut Rng::new(seed))
+            }));
+        }
+
+        // Combine the visits of every tree, counting moves in board order
+        // so ties always go the same way
+        let mut visits: HashMap<Move, u32> = HashMap::new();
+        for child in children {
+            for (square, count) in child.join().expect("search thread panicked") {
+                *visits.entry(square).or_insert(0) += count;
+            }
+        }
+        moves
+            .into_iter()
+            .rev()
+            .max_by_key(|square| visits.get(square).copied().unwrap_or(0))
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    /// The move leading here, `None` at the root.
+    square: Option<Move>,
+    /// The player who made that move.
+    player: Player,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// Legal moves without a child yet. Which ones is read off the board
+    /// when one is expanded, so nodes stay small.
+    untried: usize,
+    visits: u32,
+    /// Wins for `player`, draws count half.
+    reward: f64,
+}
+
+/// A search tree, nodes refer to each other by index.
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    fn new(game: &Game) -> Tree {
+        Tree {
+            nodes: vec![Node {
+                square: None,
+                player: game.to_move().other(),
+                parent: None,
+                children: Vec::new(),
+                untried: untried(game),
+                visits: 0,
+                reward: 0.0,
+            }],
+        }
+    }
+
+    /// Runs playouts until the budget is spent, returning the visits of
+    /// each move at the root.
+    fn grow(mut self, mut game: Game, budget: Budget, rng: &mut Rng) -> Vec<(Move, u32)> {
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            let done = match budget {
+                Budget::Iterations(limit) => iterations >= limit,
+                Budget::Time(limit) => start.elapsed() >= limit,
+            };
+            if done {
+                break;
+            }
+            self.playout(&mut game, rng);
+            iterations += 1;
+        }
+
+        self.nodes[0]
+            .children
+            .iter()
+            .map(|&child| (self.nodes[child].square.unwrap(), self.nodes[child].visits))
+            .collect()
+    }
+
+    /// One round of selection, expansion, simulation and backpropagation.
+    /// `game` is back at the root position afterwards.
+    fn playout(&mut self, game: &mut Game, rng: &mut Rng) {
+        let root_moves = game.history().len();
+
+        // Selection: follow the best child while every move has been tried
+        let mut node = 0;
+        while self.nodes[node].untried == 0 && !self.nodes[node].children.is_empty() {
+            node = self.select(node);
+            game.apply(self.nodes[node].square.unwrap())
+                .expect("tree moves are legal");
+        }
+
+        // Expansion: add one untried move
+        if self.nodes[node].untried > 0 {
+            let square = self.pick_untried(node, game, rng);
+            self.nodes[node].untried -= 1;
+            let player = game.to_move();
+            game.apply(square).expect("untried moves are legal");
+            let child = self.nodes.len();
+            self.nodes.push(Node {
+                square: Some(square),
+                player,
+                parent: Some(node),
+                children: Vec::new(),
+                untried: untried(game),
+                visits: 0,
+                reward: 0.0,
+            });
+            self.nodes[node].children.push(child);
+            node = child;
+        }
+
+        // Simulation: random moves until the game ends
+        let mut free = game.legal_moves();
+        while game.outcome() == Outcome::Ongoing {
+            let square = free.swap_remove(rng.below(free.len()));
+            game.apply(square).expect("free squares are legal");
+        }
+        let outcome = game.outcome();
+
+        // Backpropagation
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.reward += match outcome {
+                Outcome::Win(winner) if winner == node.player => 1.0,
+                Outcome::Draw => 0.5,
+                _ => 0.0,
+            };
+            current = node.parent;
+        }
+
+        while game.history().len() > root_moves {
+            game.undo();
+        }
+    }
+
+    /// A random legal move in `game` that `node` has no child for yet.
+    fn pick_untried(&self, node: usize, game: &Game, rng: &mut Rng) -> Move {
+        let rules = game.rules();
+        let mut expanded = vec![false; rules.squares()];
+        for &child in &self.nodes[node].children {
+            let square = self.nodes[child].square.unwrap();
+            expanded[square.row * rules.width + square.col] = true;
+        }
+        let nth = rng.below(self.nodes[node].untried);
+        (0..rules.squares())
+            .filter(|&i| !expanded[i])
+            .map(|i| Move::new(i / rules.width, i % rules.width))
+            .filter(|&square| game.get(square).is_none())
+            .nth(nth)
+            .expect("untried moves are empty squares")
+    }
+
+    /// The child maximising UCB1.
+    fn select(&self, node: usize) -> usize {
+        let log_visits = f64::from(self.nodes[node].visits).ln();
+        let ucb = |child: usize| {
+            let child = &self.nodes[child];
+            let visits = f64::from(child.visits);
+            child.reward / visits + EXPLORATION * (log_visits / visits).sqrt()
+        };
+        *self.nodes[node]
+            .children
+            .iter()
+            .max_by(|&&a, &&b| ucb(a).total_cmp(&ucb(b)))
+            .unwrap()
+    }
+}
+
+/// How many moves can be played in `game`, without listing them.
+fn untried(game: &Game) -> usize {
+    match game.outcome() {
+        Outcome::Ongoing => game.rules().squares() - game.history().len(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::game::Rules;
+    use super::*;
+
+    #[test]
+    fn the_same_seed_and_iterations_pick_the_same_move() {
+        let mut game = Game::with_rules(Rules::new(5, 5, 4).unwrap(), Player::X);
+        for square in [Move::new(2, 2), Move::new(1, 1), Move::new(2, 3)] {
+            game.apply(square).unwrap();
+        }
+        for threads in [1, 3] {
+            for seed in 0..5 {
+                let mcts = Mcts::new(Budget::Iterations(2000), threads, seed);
+                let first = mcts.search(&game);
+                assert!(first.is_some());
+                assert_eq!(mcts.search(&game), first, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn reads_budgets_above_zero() {
+        assert_eq!(
+            Budget::parse("500ms"),
+            Some(Budget::Time(Duration::from_millis(500)))
+        );
+        assert_eq!(
+            Budget::parse("2s"),
+            Some(Budget::Time(Duration::from_secs(2)))
+        );
+        assert_eq!(Budget::parse("300"), Some(Budget::Iterations(300)));
+        for text in ["0", "0ms", "0s", "", "ms", "-1", "1.5s", "2m"] {
+            assert_eq!(Budget::parse(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn takes_a_win_in_one() {
+        let mut game = Game::new();
+        for square in [
+            Move::new(0, 0),
+            Move::new(1, 0),
+            Move::new(0, 1),
+            Move::new(1, 1),
+        ] {
+            game.apply(square).unwrap();
+        }
+        let mcts = Mcts::new(Budget::Iterations(3000), 1, 42);
+        assert_eq!(mcts.search(&game), Some(Move::new(0, 2)));
+    }
+}
@@ -0,0 +1,459 @@
+/*
+Two players on different machines, with a server holding the game.
+
+The protocol is line based text, one message per line:
+
+client -> server
+    HELLO <name>        first line after connecting
+    MOVE <row>,<col>    play a square, 1-based, or any form `parse_move` reads
+    STATE               ask for the position again
+    QUIT                give up the game
+
+server -> client
+    HELLO <X|O> <W>x<H> <K>   your side and the rules, once both joined
+    STATE <X|O|-> <rows>      who moves next and the board, rows split
+                              by `/`, squares `.`, `X` or `O`
+    MOVE <X|O> <row>,<col>    a move that was played, sent to both
+    ERROR <message>           the last message was refused
+    RESULT <X|O|DRAW>         the game is over, the connection closes
+
+The first two clients to say HELLO play, the one that connected first
+takes X and moves first. The server checks every move against
+`Game::apply`, a client that quits or disconnects loses. A connection that
+does not say HELLO in time, or says something else, is dropped and the
+server waits for another. Lines are at most 1024 bytes, a longer one
+closes the connection.
+*/
+
+use super::game::{Game, Move, Outcome, Player, Rules};
+use super::input::parse_move;
+use super::record::parse_square;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// How long a new connection has to send its HELLO.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the server looks for new connections while players join.
+const ACCEPT_POLL: Duration = Duration::from_millis(20);
+
+/// Longest line read from a client, line ending included.
+const MAX_LINE: u64 = 1024;
+
+/// A connection that said HELLO.
+struct Joined {
+    /// Position among all accepted connections.
+    order: usize,
+    name: String,
+    address: SocketAddr,
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+/// What the reader threads pass on to the game loop.
+enum Event {
+    Line(Player, String),
+    Closed(Player),
+}
+
+/// Waits for two players on `listener` and referees one game between
+/// them, returning how it ended. Connections that fail the handshake are
+/// logged and dropped without ending the wait.
+pub fn serve(listener: &TcpListener, rules: Rules) -> io::Result<Outcome> {
+    // Handshakes run on threads of their own so a client that stays silent
+    // does not keep others from joining
+    let (join_tx, join_rx) = mpsc::channel();
+    let mut joined = Vec::with_capacity(2);
+    let mut accepted = 0;
+    listener.set_nonblocking(true)?;
+    while joined.len() < 2 {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                let (order, join_tx) = (accepted, join_tx.clone());
+                accepted += 1;
+                thread::spawn(move || greet(stream, address, order, &join_tx));
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                if let Ok(player) = join_rx.recv_timeout(ACCEPT_POLL) {
+                    println!("{} ({}) joined", player.name, player.address);
+                    joined.push(player);
+                }
+            }
+            Err(err) => eprintln!("could not accept a connection: {}", err),
+        }
+    }
+    listener.set_nonblocking(false)?;
+    // Handshakes still running find the game full
+    drop(join_rx);
+    joined.sort_by_key(|player| player.order);
+    let mut streams = Vec::with_capacity(2);
+    let mut readers = Vec::with_capacity(2);
+    let mut names = Vec::with_capacity(2);
+    for player in joined {
+        names.push(player.name);
+        streams.push(player.stream);
+        readers.push(player.reader);
+    }
+
+    // Each client gets a thread turning its lines into events, the game
+    // itself only runs on this one
+    let (tx, rx): (Sender<Event>, Receiver<Event>) = mpsc::channel();
+    for (reader, &player) in readers.into_iter().zip(&[Player::X, Player::O]) {
+        let thread_tx = tx.clone();
+        thread::spawn(move || {
+            let mut reader = reader;
+            // A line too long or not UTF-8 ends the connection too
+            while let Ok(Some(line)) = read_line(&mut reader) {
+                if thread_tx.send(Event::Line(player, line)).is_err() {
+                    return;
+                }
+            }
+            let _ = thread_tx.send(Event::Closed(player));
+        });
+    }
+
+    let mut session = Session {
+        game: Game::with_rules(rules, Player::X),
+        streams,
+    };
+    println!("{} plays X, {} plays O", names[0], names[1]);
+    for &player in &[Player::X, Player::O] {
+        let hello = format!(
+            "HELLO {} {}x{} {}",
+            player, rules.width, rules.height, rules.win_length
+        );
+        session.send(player, &hello);
+    }
+    session.broadcast(&session.state());
+
+    let outcome = session.referee(&rx);
+    let result = match outcome {
+        Outcome::Win(player) => player.to_string(),
+        _ => "DRAW".to_string(),
+    };
+    session.broadcast(&format!("RESULT {}", result));
+    println!("Result: {}", result);
+    for stream in &session.streams {
+        // Also ends the reader threads
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+    Ok(outcome)
+}
+
+/// Runs the handshake of a new connection and passes it on to `serve`, or
+/// drops it with an error.
+fn greet(stream: TcpStream, address: SocketAddr, order: usize, joined: &Sender<Joined>) {
+    // Accepted from a non-blocking listener, which some systems pass on
+    let result = stream
+        .set_nonblocking(false)
+        .and_then(|()| handshake(&stream));
+    match result {
+        Ok((name, reader)) => {
+            let player = Joined {
+                order,
+                name,
+                address,
+                stream,
+                reader,
+            };
+            if let Err(mpsc::SendError(player)) = joined.send(player) {
+                let _ = writeln!(&player.stream, "ERROR the game is full");
+            }
+        }
+        Err(err) => {
+            let _ = writeln!(&stream, "ERROR expected HELLO <name>");
+            eprintln!("dropped {}: {}", address, err);
+        }
+    }
+}
+
+/// Reads the HELLO line of a new connection, returning the player's name
+/// and the reader, which may already hold the lines after HELLO.
+fn handshake(stream: &TcpStream) -> io::Result<(String, BufReader<TcpStream>)> {
+    // A client that never says anything would otherwise hold its thread
+    stream.set_read_timeout(Some(HELLO_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let hello = read_line(&mut reader)?.unwrap_or_default();
+    let name = match hello.trim().strip_prefix("HELLO") {
+        Some(name) => name.trim().to_string(),
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected HELLO, got {:?}", hello.trim()),
+            ))
+        }
+    };
+    // Players may think as long as they like once in the game
+    stream.set_read_timeout(None)?;
+    Ok((name, reader))
+}
+
+/// A line from a client without its line ending, `None` once the client
+/// closed the connection. Lines over `MAX_LINE` bytes are refused rather
+/// than read into memory whole.
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader.by_ref().take(MAX_LINE).read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if !line.ends_with('\n') && read as u64 == MAX_LINE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line longer than {} bytes", MAX_LINE),
+        ));
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+struct Session {
+    game: Game,
+    /// X's connection first.
+    streams: Vec<TcpStream>,
+}
+
+impl Session {
+    /// Handles messages until the game is decided.
+    fn referee(&mut self, rx: &Receiver<Event>) -> Outcome {
+        loop {
+            let (player, line) = match rx.recv() {
+                Ok(Event::Line(player, line)) => (player, line),
+                Ok(Event::Closed(player)) => return Outcome::Win(player.other()),
+                Err(_) => return Outcome::Draw,
+            };
+            let line = line.trim();
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+
+            match command {
+                "STATE" => self.send(player, &self.state()),
+                "QUIT" => return Outcome::Win(player.other()),
+                "MOVE" if player != self.game.to_move() => self.send(player, "ERROR not your turn"),
+                "MOVE" => {
+                    let square = match parse_move(argument, self.game.rules()) {
+                        Ok(square) => square,
+                        Err(e) => {
+                            let message = e.to_string().replace('\n', ", ");
+                            self.send(player, &format!("ERROR {}", message));
+                            continue;
+                        }
+                    };
+                    match self.game.apply(square) {
+                        Ok(outcome) => {
+                            println!("{} plays {}", player, square);
+                            self.broadcast(&format!("MOVE {} {}", player, square));
+                            self.broadcast(&self.state());
+                            if outcome != Outcome::Ongoing {
+                                return outcome;
+                            }
+                        }
+                        Err(e) => {
+                            // Keep the reply on one line
+                            let message = e.to_string().replace('\n', ", ");
+                            self.send(player, &format!("ERROR {}", message))
+                        }
+                    }
+                }
+                _ => self.send(player, &format!("ERROR unknown command {}", command)),
+            }
+        }
+    }
+
+    fn state(&self) -> String {
+        let rules = self.game.rules();
+        let rows: Vec<String> = (0..rules.height)
+            .map(|row| {
+                (0..rules.width)
+                    .map(|col| match self.game.get(Move::new(row, col)) {
+                        Some(player) => player.symbol(),
+                        None => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+        let to_move = match self.game.outcome() {
+            Outcome::Ongoing => self.game.to_move().symbol(),
+            _ => '-',
+        };
+        format!("STATE {} {}", to_move, rows.join("/"))
+    }
+
+    /// Write errors are left to the reader thread to notice.
+    fn send(&self, player: Player, message: &str) {
+        let index = match player {
+            Player::X => 0,
+            Player::O => 1,
+        };
+        let _ = writeln!(&self.streams[index], "{}", message);
+    }
+
+    fn broadcast(&self, message: &str) {
+        self.send(Player::X, message);
+        self.send(Player::O, message);
+    }
+}
+
+/// Plays one game on a server as `name`, reading moves from `input` when
+/// it is our turn. Returns how the game ended, `Ongoing` if the server
+/// went away first.
+pub fn connect(stream: TcpStream, name: &str, mut input: impl BufRead) -> io::Result<Outcome> {
+    let mut writer = stream.try_clone()?;
+    writeln!(writer, "HELLO {}", name)?;
+    println!("Waiting for an opponent...");
+
+    let mut side = Player::X;
+    let mut game = Game::new();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+        let mut prompt = false;
+
+        match command {
+            "HELLO" => {
+                let fields: Vec<&str> = argument.split_whitespace().collect();
+                let rules = match fields.as_slice() {
+                    [player, size, k] => {
+                        side = if *player == "O" { Player::O } else { Player::X };
+                        size.split_once('x').and_then(|(w, h)| {
+                            Rules::new(w.parse().ok()?, h.parse().ok()?, k.parse().ok()?)
+                        })
+                    }
+                    _ => None,
+                };
+                game = Game::with_rules(rules.unwrap_or_default(), Player::X);
+                println!("You play {}", side);
+            }
+            "STATE" => prompt = argument.starts_with(side.symbol()),
+            "MOVE" => {
+                if let Some(square) = argument.split_whitespace().nth(1).and_then(parse_square) {
+                    let player = game.to_move();
+                    if game.apply(square).is_ok() {
+                        println!("{} plays {}", player, square);
+                    }
+                }
+                println!("{}", game);
+                if game.outcome() == Outcome::Ongoing && game.to_move() != side {
+                    println!("Waiting for {}...", game.to_move());
+                }
+            }
+            "ERROR" => {
+                println!("{}", argument);
+                prompt = game.to_move() == side;
+            }
+            "RESULT" => {
+                let outcome = match argument {
+                    "X" => Outcome::Win(Player::X),
+                    "O" => Outcome::Win(Player::O),
+                    _ => Outcome::Draw,
+                };
+                match outcome {
+                    Outcome::Win(player) if player == side => println!("You win!"),
+                    Outcome::Win(player) => println!("{} wins", player),
+                    _ => println!("Match is a draw"),
+                }
+                return Ok(outcome);
+            }
+            _ => println!("{}", line),
+        }
+
+        if prompt {
+            if game.history().is_empty() {
+                println!("{}", game);
+            }
+            println!("Your move, {} (row,col):", side);
+            let mut move_input = String::new();
+            match input.read_line(&mut move_input)? {
+                0 => writeln!(writer, "QUIT")?,
+                _ => writeln!(writer, "MOVE {}", move_input.trim())?,
+            }
+        }
+    }
+    Ok(Outcome::Ongoing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+
+    /// Joins the server at `address` as `name`, playing `moves` in turn.
+    fn player(
+        address: SocketAddr,
+        name: &'static str,
+        moves: &'static str,
+    ) -> thread::JoinHandle<Outcome> {
+        let stream = TcpStream::connect(address).unwrap();
+        thread::spawn(move || connect(stream, name, Cursor::new(moves)).unwrap())
+    }
+
+    #[test]
+    fn plays_a_game_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || serve(&listener, Rules::tic_tac_toe()).unwrap());
+
+        // Rejected without stopping the server: not UTF-8, then not HELLO
+        let mut garbage = TcpStream::connect(address).unwrap();
+        garbage.write_all(b"\xff\xfe\n").unwrap();
+        let mut rude = TcpStream::connect(address).unwrap();
+        writeln!(rude, "MOVE 1,1").unwrap();
+
+        // Connected in order so the first takes X
+        let x = player(address, "ann", "1,1\n1,2\n1,3\n");
+        let o = player(address, "bob", "2,1\n2,2\n");
+        let x_wins = Outcome::Win(Player::X);
+        assert_eq!(server.join().unwrap(), x_wins);
+        assert_eq!(x.join().unwrap(), x_wins);
+        assert_eq!(o.join().unwrap(), x_wins);
+    }
+
+    #[test]
+    fn silent_or_long_winded_clients_do_not_hold_up_the_game() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let start = std::time::Instant::now();
+        let server = thread::spawn(move || serve(&listener, Rules::tic_tac_toe()).unwrap());
+
+        // Connects first and never says a word
+        let _silent = TcpStream::connect(address).unwrap();
+        let mut long = TcpStream::connect(address).unwrap();
+        // Exactly the limit and no line ending yet, all read before the reply
+        let hello = format!("HELLO {}", "a".repeat(MAX_LINE as usize - 6));
+        long.write_all(hello.as_bytes()).unwrap();
+        let mut reply = String::new();
+        BufReader::new(&long).read_line(&mut reply).unwrap();
+        assert_eq!(reply.trim(), "ERROR expected HELLO <name>");
+
+        let x = player(address, "ann", "1,1\n1,2\n1,3\n");
+        let o = player(address, "bob", "2,1\n2,2\n");
+        let x_wins = Outcome::Win(Player::X);
+        assert_eq!(server.join().unwrap(), x_wins);
+        assert_eq!(x.join().unwrap(), x_wins);
+        assert_eq!(o.join().unwrap(), x_wins);
+        assert!(start.elapsed() < HELLO_TIMEOUT);
+    }
+
+    #[test]
+    fn refuses_lines_over_the_limit() {
+        let fits = format!("{}\n", "a".repeat(MAX_LINE as usize - 1));
+        let long = "a".repeat(MAX_LINE as usize);
+        let mut reader = Cursor::new(format!("{}{}\n", fits, long));
+        assert_eq!(
+            read_line(&mut reader).unwrap().unwrap().len(),
+            fits.len() - 1
+        );
+        assert!(read_line(&mut reader).is_err());
+        let mut reader = Cursor::new("MOVE 1,1\r\nQUIT");
+        assert_eq!(
+            read_line(&mut reader).unwrap(),
+            Some("MOVE 1,1".to_string())
+        );
+        assert_eq!(read_line(&mut reader).unwrap(), Some("QUIT".to_string()));
+        assert_eq!(read_line(&mut reader).unwrap(), None);
+    }
+}
@@ -0,0 +1,359 @@
+use super::errors::RecordError;
+use super::game::{Game, Move, Outcome, Player, Rules};
+use std::fmt::{self, Display, Formatter};
+use std::num::IntErrorKind;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A finished or unfinished game with who played it and when, written as
+/// text in the style of chess PGN:
+///
+/// ```text
+/// [Date "2024.05.01"]
+/// [X "Human"]
+/// [O "Computer (perfect)"]
+/// [Size "3x3"]
+/// [Win "3"]
+/// [First "X"]
+/// [Result "1/2-1/2"]
+///
+/// 1. 2,2 1,1 2. 3,1 1,3 3. 1,2 3,2 4. 2,1 2,3 5. 3,3 1/2-1/2
+/// ```
+///
+/// Squares are 1-based `row,col`, results read `1-0` when X wins, `0-1`
+/// when O wins, `1/2-1/2` for a draw and `*` for a game still going.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub x: String,
+    pub o: String,
+    /// `YYYY.MM.DD`, `????.??.??` when unknown.
+    pub date: String,
+    pub game: Game,
+}
+
+impl GameRecord {
+    /// Records `game` as played today.
+    pub fn new(game: Game, x: &str, o: &str) -> GameRecord {
+        GameRecord {
+            x: x.to_string(),
+            o: o.to_string(),
+            date: today(),
+            game,
+        }
+    }
+
+    pub fn name(&self, player: Player) -> &str {
+        match player {
+            Player::X => &self.x,
+            Player::O => &self.o,
+        }
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let rules = self.game.rules();
+        let result = result_token(self.game.outcome());
+        let size = format!("{}x{}", rules.width, rules.height);
+        let win = rules.win_length.to_string();
+        let first = self.game.first().to_string();
+        let headers = [
+            ("Date", self.date.as_str()),
+            ("X", self.x.as_str()),
+            ("O", self.o.as_str()),
+            ("Size", size.as_str()),
+            ("Win", win.as_str()),
+            ("First", first.as_str()),
+            ("Result", result),
+        ];
+        for (key, value) in &headers {
+            writeln!(f, "[{} \"{}\"]", key, escape(value))?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        for (ply, square) in self.game.history().iter().enumerate() {
+            if ply.is_multiple_of(2) {
+                tokens.push(format!("{}.", ply / 2 + 1));
+            }
+            tokens.push(square.to_string());
+        }
+        tokens.push(result.to_string());
+
+        // Wrap the move list the way PGN files usually are
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 79 {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+/// Writes every game, separated by blank lines.
+pub fn write_session(records: &[GameRecord]) -> String {
+    records
+        .iter()
+        .map(|record| record.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Reads the games written by `write_session`, replaying each one so that
+/// illegal moves or a wrong result are reported.
+pub fn parse_session(text: &str) -> Result<Vec<GameRecord>, RecordError> {
+    let mut records = Vec::new();
+    let mut pending = Pending::default();
+
+    for (e, line) in text.lines().enumerate() {
+        let line_nr = e + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            // A header after moves starts the next game
+            if pending.has_moves() {
+                records.push(pending.finish()?);
+                pending = Pending::default();
+            }
+            let (key, value) = parse_header(line).ok_or_else(|| RecordError::Syntax {
+                line: line_nr,
+                text: line.to_string(),
+            })?;
+            pending.headers.push((key, value, line_nr));
+            continue;
+        }
+        for token in line.split_whitespace() {
+            if token.ends_with('.') && token[..token.len() - 1].parse::<usize>().is_ok() {
+                continue;
+            }
+            if parse_result(token).is_some() {
+                pending.terminator = Some((token.to_string(), line_nr));
+                continue;
+            }
+            let square = parse_square(token).ok_or_else(|| RecordError::Syntax {
+                line: line_nr,
+                text: token.to_string(),
+            })?;
+            pending.moves.push((square, line_nr));
+        }
+    }
+    if pending.has_moves() || !pending.headers.is_empty() {
+        records.push(pending.finish()?);
+    }
+    Ok(records)
+}
+
+/// A size or win length. Numbers too large for `usize` saturate, so that
+/// `Rules::new` rejects them as a board too large rather than as a typo.
+fn parse_count(text: &str) -> Option<usize> {
+    match text.parse::<usize>() {
+        Ok(count) => Some(count),
+        Err(error) if *error.kind() == IntErrorKind::PosOverflow => Some(usize::MAX),
+        Err(_) => None,
+    }
+}
+
+/// A game whose lines have been read but not checked yet.
+#[derive(Default)]
+struct Pending {
+    headers: Vec<(String, String, usize)>,
+    moves: Vec<(Move, usize)>,
+    terminator: Option<(String, usize)>,
+}
+
+impl Pending {
+    fn has_moves(&self) -> bool {
+        !self.moves.is_empty() || self.terminator.is_some()
+    }
+
+    fn header(&self, key: &str) -> Option<(&str, usize)> {
+        self.headers
+            .iter()
+            .find(|(k, _, _)| k == key)
+            .map(|(_, value, line)| (value.as_str(), *line))
+    }
+
+    fn finish(self) -> Result<GameRecord, RecordError> {
+        let invalid = |(value, line): (&str, usize)| RecordError::Syntax {
+            line,
+            text: value.to_string(),
+        };
+
+        let (width, height) = match self.header("Size") {
+            Some(header) => header
+                .0
+                .split_once('x')
+                .and_then(|(w, h)| Some((parse_count(w)?, parse_count(h)?)))
+                .ok_or_else(|| invalid(header))?,
+            None => (3, 3),
+        };
+        let win_length = match self.header("Win") {
+            Some(header) => parse_count(header.0).ok_or_else(|| invalid(header))?,
+            None => 3,
+        };
+        let rules = Rules::new(width, height, win_length).ok_or(RecordError::InvalidRules)?;
+        let first = match self.header("First") {
+            Some(("X", _)) | None => Player::X,
+            Some(("O", _)) => Player::O,
+            Some(header) => return Err(invalid(header)),
+        };
+
+        let mut game = Game::with_rules(rules, first);
+        for (ply, &(square, line)) in self.moves.iter().enumerate() {
+            game.apply(square)
+                .map_err(|error| RecordError::IllegalMove {
+                    line,
+                    ply: ply + 1,
+                    error,
+                })?;
+        }
+
+        // Both the header and the end of the move list may state a result,
+        // and both have to agree with the moves
+        let recorded = self.header("Result").into_iter().chain(
+            self.terminator
+                .as_ref()
+                .map(|(token, line)| (token.as_str(), *line)),
+        );
+        for header in recorded {
+            match parse_result(header.0) {
+                Some(outcome) if outcome == game.outcome() => (),
+                Some(_) => {
+                    return Err(RecordError::ResultMismatch {
+                        line: header.1,
+                        recorded: header.0.to_string(),
+                    })
+                }
+                None => return Err(invalid(header)),
+            }
+        }
+
+        let text = |key: &str, default: &str| {
+            self.header(key)
+                .map_or(default.to_string(), |(value, _)| value.to_string())
+        };
+        Ok(GameRecord {
+            x: text("X", "?"),
+            o: text("O", "?"),
+            date: text("Date", "????.??.??"),
+            game,
+        })
+    }
+}
+
+fn result_token(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Win(Player::X) => "1-0",
+        Outcome::Win(Player::O) => "0-1",
+        Outcome::Draw => "1/2-1/2",
+        Outcome::Ongoing => "*",
+    }
+}
+
+fn parse_result(token: &str) -> Option<Outcome> {
+    match token {
+        "1-0" => Some(Outcome::Win(Player::X)),
+        "0-1" => Some(Outcome::Win(Player::O)),
+        "1/2-1/2" => Some(Outcome::Draw),
+        "*" => Some(Outcome::Ongoing),
+        _ => None,
+    }
+}
+
+/// A 1-based `row,col` as written by `Move`'s `Display`.
+pub(super) fn parse_square(token: &str) -> Option<Move> {
+    let (row, col) = token.split_once(',')?;
+    let row = row.parse::<usize>().ok()?.checked_sub(1)?;
+    let col = col.parse::<usize>().ok()?.checked_sub(1)?;
+    Some(Move::new(row, col))
+}
+
+/// `[Key "Value"]`, with `\"` and `\\` escaped inside the value.
+fn parse_header(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (key, quoted) = inner.split_once(' ')?;
+    let quoted = quoted.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?),
+            '"' => return None,
+            c => value.push(c),
+        }
+    }
+    Some((key.to_string(), value))
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The current UTC date as `YYYY.MM.DD`.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date, after
+/// Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_a_session() {
+        let mut game = Game::new();
+        for square in [Move::new(1, 1), Move::new(0, 0), Move::new(2, 0)] {
+            game.apply(square).unwrap();
+        }
+        let record = GameRecord::new(game, "Ann", "Bob \"the\" bot");
+        let parsed = parse_session(&write_session(std::slice::from_ref(&record))).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].o, record.o);
+        assert_eq!(parsed[0].game.history(), record.game.history());
+    }
+
+    #[test]
+    fn rejects_oversized_boards_as_invalid_rules() {
+        for size in [
+            "4294967296x4294967296",
+            "99999999999999999999999x3",
+            "1000x1000",
+        ] {
+            let text = format!("[Size \"{}\"]\n[Win \"3\"]\n\n*\n", size);
+            assert_eq!(
+                parse_session(&text).unwrap_err(),
+                RecordError::InvalidRules,
+                "{}",
+                size
+            );
+        }
+        let typo = parse_session("[Size \"3y3\"]\n\n*\n").unwrap_err();
+        assert!(matches!(typo, RecordError::Syntax { line: 1, .. }));
+    }
+}
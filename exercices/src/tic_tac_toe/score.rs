@@ -0,0 +1,74 @@
+use super::game::{Outcome, Player};
+use std::fmt::{self, Display, Formatter};
+
+/// Games won by each side and drawn over a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub x: u32,
+    pub o: u32,
+    pub draws: u32,
+}
+
+impl Score {
+    /// Counts a finished game, unfinished ones are ignored.
+    pub fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Win(Player::X) => self.x += 1,
+            Outcome::Win(Player::O) => self.o += 1,
+            Outcome::Draw => self.draws += 1,
+            Outcome::Ongoing => (),
+        }
+    }
+
+    pub fn wins(&self, player: Player) -> u32 {
+        match player {
+            Player::X => self.x,
+            Player::O => self.o,
+        }
+    }
+
+    /// Finished games, draws included.
+    pub fn games(&self) -> u32 {
+        self.x + self.o + self.draws
+    }
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Score:\n\tX: {}\n\tO: {}\n\tDraws: {}",
+            self.x, self.o, self.draws
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_finished_games() {
+        let mut score = Score::default();
+        for outcome in [
+            Outcome::Win(Player::X),
+            Outcome::Draw,
+            Outcome::Win(Player::O),
+            Outcome::Win(Player::X),
+            Outcome::Ongoing,
+        ] {
+            score.record(outcome);
+        }
+        assert_eq!(
+            score,
+            Score {
+                x: 2,
+                o: 1,
+                draws: 1
+            }
+        );
+        assert_eq!(score.wins(Player::O), 1);
+        assert_eq!(score.games(), 4);
+        assert_eq!(score.to_string(), "Score:\n\tX: 2\n\tO: 1\n\tDraws: 1");
+    }
+}
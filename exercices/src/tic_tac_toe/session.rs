@@ -0,0 +1,222 @@
+use super::ai::Ai;
+use super::errors::MoveError;
+use super::game::{Game, Move, Outcome, Player};
+use super::record::{parse_session, write_session, GameRecord};
+use super::score::Score;
+use super::Config;
+use std::fs;
+
+/// A run of games in the terminal: the game being played, the ones before
+/// it and the computer, whatever the front end.
+pub struct Session<'a> {
+    config: &'a Config,
+    ai: Option<Ai>,
+    names: (String, String),
+    records: Vec<GameRecord>,
+    game: Game,
+    score: Score,
+    /// Whether every save so far succeeded.
+    saved: bool,
+}
+
+impl<'a> Session<'a> {
+    /// Loads the configured session file, if any.
+    pub fn start(config: &'a Config) -> Result<Session<'a>, String> {
+        let ai = config.ai.map(|difficulty| {
+            let mut ai = match config.seed {
+                Some(seed) => Ai::with_seed(difficulty, seed),
+                None => Ai::new(difficulty),
+            };
+            if let Some(budget) = config.budget {
+                ai = ai.with_budget(budget);
+            }
+            if let Some(threads) = config.threads {
+                ai = ai.with_threads(threads);
+            }
+            ai
+        });
+        let name = |player: Player| match &ai {
+            Some(ai) if player == config.ai_plays => format!("Computer ({})", ai.difficulty()),
+            _ => "Human".to_string(),
+        };
+        let names = (name(Player::X), name(Player::O));
+
+        let mut records = match &config.load {
+            Some(path) => load_session(path)?,
+            None => Vec::new(),
+        };
+        // The first move alternates from one game to the next
+        let game = match records.last() {
+            Some(record) if record.game.outcome() == Outcome::Ongoing => {
+                records.pop().unwrap().game
+            }
+            Some(record) => Game::with_rules(config.rules, record.game.first().other()),
+            None => Game::with_rules(config.rules, Player::X),
+        };
+        let mut score = Score::default();
+        for record in &records {
+            score.record(record.game.outcome());
+        }
+
+        Ok(Session {
+            config,
+            ai,
+            names,
+            records,
+            game,
+            score,
+            saved: true,
+        })
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn score(&self) -> Score {
+        self.score
+    }
+
+    pub fn ai(&self) -> Option<&Ai> {
+        self.ai.as_ref()
+    }
+
+    /// The last finished game, while the next one has not started.
+    pub fn last_game(&self) -> Option<&Game> {
+        match self.records.last() {
+            Some(record) if self.game.history().is_empty() => Some(&record.game),
+            _ => None,
+        }
+    }
+
+    /// Whether the computer plays the side to move.
+    pub fn computer_to_move(&self) -> bool {
+        self.ai.is_some() && self.game.to_move() == self.config.ai_plays
+    }
+
+    /// The computer's choice for the side to move.
+    pub fn computer_move(&mut self) -> Option<Move> {
+        let game = &self.game;
+        self.ai.as_mut().and_then(|ai| ai.choose(game))
+    }
+
+    /// Plays `square`. A finished game is recorded, scored and saved, and
+    /// the next one set up with the other side moving first.
+    pub fn play(&mut self, square: Move) -> Result<Outcome, MoveError> {
+        let outcome = self.game.apply(square)?;
+        if outcome != Outcome::Ongoing {
+            self.score.record(outcome);
+            let next = Game::with_rules(self.config.rules, self.game.first().other());
+            let game = std::mem::replace(&mut self.game, next);
+            self.records
+                .push(GameRecord::new(game, &self.names.0, &self.names.1));
+            self.save();
+        }
+        Ok(outcome)
+    }
+
+    /// Saves the session with the game in progress. Returns `false` if
+    /// this or any earlier save failed.
+    pub fn finish(mut self) -> bool {
+        if !self.game.history().is_empty() {
+            let game = self.game.clone();
+            self.records
+                .push(GameRecord::new(game, &self.names.0, &self.names.1));
+        }
+        self.save();
+        self.saved
+    }
+
+    /// Writes the records to the configured file, if any.
+    fn save(&mut self) {
+        if let Some(path) = &self.config.save {
+            if let Err(err) = fs::write(path, write_session(&self.records)) {
+                eprintln!("{}: {}", path, err);
+                self.saved = false;
+            }
+        }
+    }
+}
+
+pub fn load_session(path: &str) -> Result<Vec<GameRecord>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    parse_session(&text).map_err(|err| format!("{}: {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// X takes the top row while O plays below it.
+    const X_WINS: [Move; 5] = [
+        Move { row: 0, col: 0 },
+        Move { row: 1, col: 0 },
+        Move { row: 0, col: 1 },
+        Move { row: 1, col: 1 },
+        Move { row: 0, col: 2 },
+    ];
+
+    fn play_game(session: &mut Session) -> Outcome {
+        let mut outcome = Outcome::Ongoing;
+        let mut squares = X_WINS.iter();
+        while outcome == Outcome::Ongoing {
+            // Whoever starts takes the top row
+            outcome = session.play(*squares.next().unwrap()).unwrap();
+        }
+        outcome
+    }
+
+    #[test]
+    fn alternates_the_first_move_and_keeps_score() {
+        let config = Config::default();
+        let mut session = Session::start(&config).unwrap();
+        assert_eq!(session.game().first(), Player::X);
+        assert_eq!(play_game(&mut session), Outcome::Win(Player::X));
+        assert_eq!(session.game().first(), Player::O);
+        assert_eq!(session.last_game().map(Game::first), Some(Player::X));
+        assert_eq!(play_game(&mut session), Outcome::Win(Player::O));
+        assert_eq!(session.game().first(), Player::X);
+        assert_eq!(
+            session.score(),
+            Score {
+                x: 1,
+                o: 1,
+                draws: 0
+            }
+        );
+    }
+
+    #[test]
+    fn picks_up_a_saved_session() {
+        let path = env::temp_dir().join(format!("ttt-session-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let config = Config {
+            save: Some(path.clone()),
+            ..Config::default()
+        };
+        let mut session = Session::start(&config).unwrap();
+        play_game(&mut session);
+        assert!(session.finish());
+
+        // The next game is O's to start
+        let config = Config {
+            load: Some(path.clone()),
+            save: Some(path.clone()),
+            ..Config::default()
+        };
+        let mut session = Session::start(&config).unwrap();
+        assert_eq!(session.game().first(), Player::O);
+        assert_eq!(session.score().x, 1);
+
+        // A game left unfinished is resumed as it was
+        session.play(Move::new(2, 2)).unwrap();
+        assert!(session.finish());
+        let session = Session::start(&config).unwrap();
+        assert_eq!(session.game().first(), Player::O);
+        assert_eq!(session.game().history(), [Move::new(2, 2)]);
+        assert_eq!(session.score().games(), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
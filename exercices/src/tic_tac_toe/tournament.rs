@@ -0,0 +1,329 @@
+use super::agent::Agent;
+use super::game::{Game, Outcome, Player, Rules};
+use std::io::{self, Write};
+
+/// Rating every agent starts from.
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// Games and rating of one agent over a tournament.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub name: String,
+    pub rating: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Standing {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points scored, a win counting 1 and a draw half.
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+}
+
+/// How two agents did against each other, from the first one's side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pairing {
+    /// Indexes into the agents.
+    pub first: usize,
+    pub second: usize,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Every agent meets every other one for `games` games, taking turns at
+/// playing X, who moves first. Ratings follow the Elo system and are
+/// updated after each game.
+#[derive(Debug, Clone, Copy)]
+pub struct Tournament {
+    pub rules: Rules,
+    /// Games per pairing.
+    pub games: u32,
+    /// Largest rating change a single game can bring.
+    pub k: f64,
+}
+
+impl Tournament {
+    pub fn new(rules: Rules, games: u32) -> Tournament {
+        Tournament {
+            rules,
+            games,
+            k: 32.0,
+        }
+    }
+
+    /// Plays all pairings in order, returning their results and the
+    /// standings, both indexed like `agents`.
+    pub fn play(&self, agents: &mut [Box<dyn Agent>]) -> (Vec<Pairing>, Vec<Standing>) {
+        let mut standings: Vec<Standing> = agents
+            .iter()
+            .map(|agent| Standing {
+                name: agent.name(),
+                rating: INITIAL_RATING,
+                wins: 0,
+                draws: 0,
+                losses: 0,
+            })
+            .collect();
+        let mut pairings = Vec::new();
+
+        for first in 0..agents.len() {
+            for second in first + 1..agents.len() {
+                let mut pairing = Pairing {
+                    first,
+                    second,
+                    wins: 0,
+                    draws: 0,
+                    losses: 0,
+                };
+                for number in 0..self.games {
+                    // Split so both agents can be borrowed at once
+                    let (head, tail) = agents.split_at_mut(second);
+                    let (a, b) = (&mut head[first], &mut tail[0]);
+                    let (outcome, a_plays) = if number.is_multiple_of(2) {
+                        (play_game(a.as_mut(), b.as_mut(), self.rules).0, Player::X)
+                    } else {
+                        (play_game(b.as_mut(), a.as_mut(), self.rules).0, Player::O)
+                    };
+
+                    // 1 for a win of the first agent, 0 for a loss
+                    let result = match outcome {
+                        Outcome::Win(player) if player == a_plays => {
+                            pairing.wins += 1;
+                            standings[first].wins += 1;
+                            standings[second].losses += 1;
+                            1.0
+                        }
+                        Outcome::Win(_) => {
+                            pairing.losses += 1;
+                            standings[first].losses += 1;
+                            standings[second].wins += 1;
+                            0.0
+                        }
+                        _ => {
+                            pairing.draws += 1;
+                            standings[first].draws += 1;
+                            standings[second].draws += 1;
+                            0.5
+                        }
+                    };
+                    let expected = expected(standings[first].rating, standings[second].rating);
+                    let change = self.k * (result - expected);
+                    standings[first].rating += change;
+                    standings[second].rating -= change;
+                }
+                pairings.push(pairing);
+            }
+        }
+        (pairings, standings)
+    }
+}
+
+/// The score `rating` is expected to make against `opponent`, between 0
+/// and 1.
+pub fn expected(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// Plays one game, X moving first. An agent that resigns or picks an
+/// illegal square loses, so the outcome can differ from the final
+/// position's.
+pub fn play_game(x: &mut dyn Agent, o: &mut dyn Agent, rules: Rules) -> (Outcome, Game) {
+    let mut game = Game::with_rules(rules, Player::X);
+    let mut outcome = Outcome::Ongoing;
+    while outcome == Outcome::Ongoing {
+        let player = game.to_move();
+        let choice = match player {
+            Player::X => x.choose(&game),
+            Player::O => o.choose(&game),
+        };
+        outcome = match choice.map(|square| game.apply(square)) {
+            Some(Ok(outcome)) => outcome,
+            Some(Err(_)) | None => Outcome::Win(player.other()),
+        };
+    }
+    x.game_over(&game, outcome);
+    o.game_over(&game, outcome);
+    (outcome, game)
+}
+
+/// Prints each pairing, then the agents by rating.
+pub fn write_results(
+    pairings: &[Pairing],
+    standings: &[Standing],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let names: Vec<&str> = standings.iter().map(|s| s.name.as_str()).collect();
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    writeln!(out, "Results (wins-draws-losses of the first)")?;
+    for pairing in pairings {
+        writeln!(
+            out,
+            "{:>width$} vs {:<width$} {}-{}-{}",
+            names[pairing.first],
+            names[pairing.second],
+            pairing.wins,
+            pairing.draws,
+            pairing.losses,
+            width = width
+        )?;
+    }
+
+    writeln!(
+        out,
+        "\n{:>4}  {:<width$} {:>6} {:>6} {:>6} {:>6} {:>6} {:>6}",
+        "rank",
+        "agent",
+        "rating",
+        "games",
+        "wins",
+        "draws",
+        "losses",
+        "points",
+        width = width
+    )?;
+    let mut ranked: Vec<&Standing> = standings.iter().collect();
+    ranked.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+    for (rank, standing) in ranked.iter().enumerate() {
+        writeln!(
+            out,
+            "{:>4}  {:<width$} {:>6.0} {:>6} {:>6} {:>6} {:>6} {:>6}",
+            rank + 1,
+            standing.name,
+            standing.rating,
+            standing.games(),
+            standing.wins,
+            standing.draws,
+            standing.losses,
+            standing.points(),
+            width = width
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::agent::Scripted;
+    use super::super::game::Move;
+    use super::*;
+
+    /// Gives up at once.
+    struct Resigner;
+
+    impl Agent for Resigner {
+        fn name(&self) -> String {
+            "resigner".to_string()
+        }
+
+        fn choose(&mut self, _game: &Game) -> Option<Move> {
+            None
+        }
+    }
+
+    /// Always plays off the board.
+    struct Cheater;
+
+    impl Agent for Cheater {
+        fn name(&self) -> String {
+            "cheater".to_string()
+        }
+
+        fn choose(&mut self, _game: &Game) -> Option<Move> {
+            Some(Move::new(9, 9))
+        }
+    }
+
+    fn scripted(name: &str, squares: &[(usize, usize)]) -> Box<dyn Agent> {
+        let moves = squares.iter().map(|&(row, col)| Move::new(row, col));
+        Box::new(Scripted::new(name, moves.collect()))
+    }
+
+    #[test]
+    fn counts_results_from_the_first_agent() {
+        // Whoever plays X completes their row first
+        let mut agents = vec![
+            scripted("top", &[(0, 0), (0, 1), (0, 2)]),
+            scripted("middle", &[(1, 0), (1, 1), (1, 2)]),
+        ];
+        let (pairings, standings) = Tournament::new(Rules::tic_tac_toe(), 4).play(&mut agents);
+        assert_eq!(
+            pairings,
+            [Pairing {
+                first: 0,
+                second: 1,
+                wins: 2,
+                draws: 0,
+                losses: 2
+            }]
+        );
+        assert_eq!((standings[0].wins, standings[0].losses), (2, 2));
+        assert_eq!((standings[1].wins, standings[1].losses), (2, 2));
+
+        let mut agents = vec![
+            scripted("x", &[(1, 1), (0, 1), (1, 0), (0, 2), (2, 2)]),
+            scripted("o", &[(0, 0), (2, 1), (1, 2), (2, 0)]),
+        ];
+        let (pairings, standings) = Tournament::new(Rules::tic_tac_toe(), 1).play(&mut agents);
+        assert_eq!((pairings[0].wins, pairings[0].draws), (0, 1));
+        assert_eq!(standings[0].points(), 0.5);
+        assert_eq!(standings[0].rating, INITIAL_RATING);
+    }
+
+    #[test]
+    fn an_illegal_move_or_resigning_loses() {
+        let rules = Rules::tic_tac_toe();
+        let mut other = Scripted::new("other", Vec::new());
+
+        let (outcome, game) = play_game(&mut Cheater, &mut other, rules);
+        assert_eq!(outcome, Outcome::Win(Player::O));
+        assert!(game.history().is_empty());
+        let (outcome, _) = play_game(&mut other, &mut Resigner, rules);
+        assert_eq!(outcome, Outcome::Win(Player::X));
+
+        let mut agents: Vec<Box<dyn Agent>> = vec![
+            Box::new(Resigner),
+            scripted("other", &[]),
+            Box::new(Cheater),
+        ];
+        let (pairings, standings) = Tournament::new(rules, 2).play(&mut agents);
+        assert_eq!((pairings[0].wins, pairings[0].losses), (0, 2));
+        // Each loses the game it moves first in
+        assert_eq!((pairings[1].wins, pairings[1].losses), (1, 1));
+        assert_eq!(standings[1].wins, 4);
+    }
+
+    #[test]
+    fn rating_changes_cancel_out() {
+        let mut agents = vec![
+            scripted("top", &[(0, 0), (0, 1), (0, 2)]),
+            scripted("middle", &[(1, 0), (1, 1), (1, 2)]),
+            Box::new(Resigner),
+        ];
+        let (_, standings) = Tournament::new(Rules::tic_tac_toe(), 3).play(&mut agents);
+        let total: f64 = standings.iter().map(|standing| standing.rating).sum();
+        assert!((total - 3.0 * INITIAL_RATING).abs() < 1e-9);
+        assert!(standings[2].rating < INITIAL_RATING);
+
+        // One game between equals moves both by half of k
+        let mut agents = vec![
+            scripted("top", &[(0, 0), (0, 1), (0, 2)]),
+            Box::new(Cheater),
+        ];
+        let tournament = Tournament::new(Rules::tic_tac_toe(), 1);
+        let (_, standings) = tournament.play(&mut agents);
+        assert_eq!(standings[0].rating, INITIAL_RATING + tournament.k / 2.0);
+        assert_eq!(standings[1].rating, INITIAL_RATING - tournament.k / 2.0);
+    }
+}
@@ -0,0 +1,356 @@
+use super::game::{Game, Move, Outcome, Player, Rules};
+use super::session::Session;
+use super::Config;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor};
+use crossterm::{cursor, queue, terminal};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Where the board starts on screen.
+const BOARD_TOP: u16 = 3;
+
+/// Puts the terminal back however the UI is left, panics included.
+struct Screen;
+
+impl Screen {
+    fn enter(out: &mut impl Write) -> io::Result<Screen> {
+        terminal::enable_raw_mode()?;
+        queue!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        out.flush()?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        let _ = queue!(out, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = out.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// What the screen shows besides the board.
+struct View {
+    cursor: Move,
+    /// A finished game kept on screen until a key is pressed.
+    finished: Option<Game>,
+    message: String,
+    /// What the last frame put on screen.
+    drawn: Drawn,
+}
+
+/// How a square looks on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    stone: Option<Player>,
+    background: Option<Color>,
+    cursor: bool,
+}
+
+/// What is on screen, so that a frame only rewrites what changed.
+#[derive(Default)]
+struct Drawn {
+    /// The terminal size and rules the screen was laid out for, `None`
+    /// when it holds something else.
+    layout: Option<(u16, u16, Rules)>,
+    /// Row by row.
+    cells: Vec<Cell>,
+    /// The text lines by screen row.
+    lines: HashMap<u16, String>,
+}
+
+/// Full-screen play: arrow keys move the cursor, Enter or Space plays the
+/// square under it and `q` quits. Returns `false` if the session could not
+/// be loaded or saved.
+pub fn run(config: &Config) -> bool {
+    let mut session = match Session::start(config) {
+        Ok(session) => session,
+        Err(message) => {
+            eprintln!("{}", message);
+            return false;
+        }
+    };
+    let result = play(&mut session);
+    println!("{}", session.score());
+    match result {
+        Ok(()) => session.finish(),
+        Err(err) => {
+            eprintln!("{}", err);
+            session.finish();
+            false
+        }
+    }
+}
+
+fn play(session: &mut Session) -> io::Result<()> {
+    let mut out = io::stdout();
+    let _screen = Screen::enter(&mut out)?;
+    let rules = session.game().rules();
+    let mut view = View {
+        cursor: Move::new(rules.height / 2, rules.width / 2),
+        finished: None,
+        message: String::new(),
+        drawn: Drawn::default(),
+    };
+
+    loop {
+        if view.finished.is_none() && session.computer_to_move() {
+            view.message = "The computer is thinking...".to_string();
+            // Waits for a larger terminal below otherwise
+            if draw(&mut out, session, &mut view)? {
+                if let Some(square) = session.computer_move() {
+                    let result = session.play(square);
+                    after_move(session, &mut view, result.map_err(|e| e.to_string()));
+                }
+                continue;
+            }
+        }
+        let fits = draw(&mut out, session, &mut view)?;
+
+        // Resizing comes as an event too, and draws the next frame
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        if is_quit(key) {
+            return Ok(());
+        }
+        if !fits {
+            continue;
+        }
+        if view.finished.take().is_some() {
+            // The next game may be a different size than a resumed one
+            let rules = session.game().rules();
+            view.cursor.row = view.cursor.row.min(rules.height - 1);
+            view.cursor.col = view.cursor.col.min(rules.width - 1);
+            view.message.clear();
+            continue;
+        }
+
+        let rules = session.game().rules();
+        let Move { row, col } = view.cursor;
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => view.cursor.row = row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => view.cursor.row = (row + 1).min(rules.height - 1),
+            KeyCode::Left | KeyCode::Char('h') => view.cursor.col = col.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => view.cursor.col = (col + 1).min(rules.width - 1),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let result = session.play(view.cursor);
+                after_move(session, &mut view, result.map_err(|e| e.to_string()));
+            }
+            _ => (),
+        }
+    }
+}
+
+fn is_quit(key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => true,
+        KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
+fn after_move(session: &Session, view: &mut View, result: Result<Outcome, String>) {
+    view.message = match result {
+        Ok(Outcome::Ongoing) => String::new(),
+        Ok(outcome) => {
+            view.finished = session.last_game().cloned();
+            let verdict = match outcome {
+                Outcome::Win(player) => format!("{} wins!", player),
+                _ => "Match is a draw.".to_string(),
+            };
+            format!("{} Press any key for the next game", verdict)
+        }
+        // Move errors are worded for the line based game, keep the first line
+        Err(message) => message.lines().next().unwrap_or_default().to_string(),
+    };
+}
+
+/// Draws the next frame, returning `false` when the terminal is too small
+/// to show the board.
+fn draw(out: &mut impl Write, session: &Session, view: &mut View) -> io::Result<bool> {
+    let game = view.finished.as_ref().unwrap_or_else(|| session.game());
+    let rules = game.rules();
+    let (width, height) = terminal::size()?;
+
+    // The board, the message under it and the two status rows
+    let margin = rules.height.to_string().len() + 1;
+    let needed = (
+        margin + 4 * rules.width + 1,
+        BOARD_TOP as usize + 2 * rules.height + 6,
+    );
+    if (width as usize) < needed.0 || (height as usize) < needed.1 {
+        view.drawn = Drawn::default();
+        queue!(
+            out,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0),
+            Print(format!(
+                "The {}x{} board needs a terminal of {}x{}, this one is {}x{}.",
+                rules.width, rules.height, needed.0, needed.1, width, height
+            )),
+            cursor::MoveTo(0, 1),
+            Print("Make it larger, or press q to quit."),
+        )?;
+        out.flush()?;
+        return Ok(false);
+    }
+
+    // Everything that only changes with the layout
+    let layout = Some((width, height, rules));
+    if view.drawn.layout != layout {
+        view.drawn = Drawn {
+            layout,
+            ..Drawn::default()
+        };
+        let labels: String = (1..=rules.width)
+            .map(|col| format!("  {:<2}", col))
+            .collect();
+        let separator = format!("{}{}-", " ".repeat(margin), "----".repeat(rules.width));
+        queue!(
+            out,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0),
+            SetAttribute(Attribute::Bold),
+            Print("TIC TAC TOE YO"),
+            SetAttribute(Attribute::Reset),
+            cursor::MoveTo(0, BOARD_TOP),
+            Print(format!("{}{}", " ".repeat(margin), labels.trim_end())),
+        )?;
+        for row in 0..rules.height {
+            let line = BOARD_TOP + 1 + 2 * row as u16;
+            queue!(
+                out,
+                cursor::MoveTo(0, line),
+                Print(&separator),
+                cursor::MoveTo(0, line + 1),
+                Print(format!(
+                    "{:<width$}{}|",
+                    row + 1,
+                    "|   ".repeat(rules.width),
+                    width = margin
+                )),
+            )?;
+        }
+        queue!(
+            out,
+            cursor::MoveTo(0, BOARD_TOP + 1 + 2 * rules.height as u16),
+            Print(&separator),
+            cursor::MoveTo(0, height - 2),
+            Print("arrows/hjkl move  enter/space play  q quit"),
+        )?;
+    }
+
+    let mut header = format!(
+        "{}x{} board, {} in a row wins",
+        rules.width, rules.height, rules.win_length
+    );
+    if let Some(ai) = session.ai() {
+        header.push_str(&format!(", the computer plays {}", ai.difficulty()));
+    }
+    draw_line(out, &mut view.drawn, 1, header, false)?;
+
+    // Squares worth pointing out
+    let winning = match game.outcome() {
+        Outcome::Win(_) => game
+            .last_move()
+            .and_then(|square| game.winning_line(square))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let show_cursor = view.finished.is_none() && !session.computer_to_move();
+    let cells: Vec<Cell> = (0..rules.height)
+        .flat_map(|row| (0..rules.width).map(move |col| Move::new(row, col)))
+        .map(|square| Cell {
+            stone: game.get(square),
+            background: if winning.contains(&square) {
+                Some(Color::DarkGreen)
+            } else if game.last_move() == Some(square) {
+                Some(Color::DarkYellow)
+            } else {
+                None
+            },
+            cursor: show_cursor && view.cursor == square,
+        })
+        .collect();
+    for (i, cell) in cells.iter().enumerate() {
+        if view.drawn.cells.get(i) == Some(cell) {
+            continue;
+        }
+        let (row, col) = (i / rules.width, i % rules.width);
+        queue!(
+            out,
+            cursor::MoveTo(
+                (margin + 4 * col + 1) as u16,
+                BOARD_TOP + 2 + 2 * row as u16
+            )
+        )?;
+        if let Some(color) = cell.background {
+            queue!(out, SetBackgroundColor(color))?;
+        }
+        if cell.cursor {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        }
+        let text = match cell.stone {
+            Some(player) => format!(" {} ", player),
+            None => "   ".to_string(),
+        };
+        queue!(out, Print(text), SetAttribute(Attribute::Reset), ResetColor)?;
+    }
+    view.drawn.cells = cells;
+
+    let message_line = BOARD_TOP + 3 + 2 * rules.height as u16;
+    draw_line(
+        out,
+        &mut view.drawn,
+        message_line,
+        view.message.clone(),
+        false,
+    )?;
+
+    // Status bar on the last row, under the keys
+    let score = session.score();
+    let turn = match game.outcome() {
+        Outcome::Ongoing => format!("{} to move", game.to_move()),
+        _ => "game over".to_string(),
+    };
+    let status = format!(
+        " X {}  O {}  Draws {} | Game {}, round {} | {} ",
+        score.wins(Player::X),
+        score.wins(Player::O),
+        score.draws,
+        score.games() + view.finished.is_none() as u32,
+        game.history().len() + view.finished.is_none() as usize,
+        turn
+    );
+    draw_line(out, &mut view.drawn, height - 1, status, true)?;
+    out.flush()?;
+    Ok(true)
+}
+
+/// Rewrites screen row `y` if its text changed since the last frame.
+fn draw_line(
+    out: &mut impl Write,
+    drawn: &mut Drawn,
+    y: u16,
+    text: String,
+    reverse: bool,
+) -> io::Result<()> {
+    if drawn.lines.get(&y) == Some(&text) {
+        return Ok(());
+    }
+    queue!(
+        out,
+        cursor::MoveTo(0, y),
+        terminal::Clear(terminal::ClearType::UntilNewLine)
+    )?;
+    if reverse {
+        queue!(out, SetAttribute(Attribute::Reverse))?;
+    }
+    queue!(out, Print(&text), SetAttribute(Attribute::Reset))?;
+    drawn.lines.insert(y, text);
+    Ok(())
+}
@@ -0,0 +1,374 @@
+use super::errors::MoveError;
+use super::game::{Game, Move, Outcome, Player, Rules};
+use super::input::parse_ultimate_move;
+use super::score::Score;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+/// Ultimate tic-tac-toe: a 3x3 grid of 3x3 boards. The square played in a
+/// board picks the board the opponent plays in next, unless that one is
+/// already decided, then any open board will do. Winning a board claims
+/// its square of the grid, three claimed in a row win the game.
+///
+/// Squares are counted on the whole 9x9 grid.
+#[derive(Debug, Clone)]
+pub struct Ultimate {
+    /// The small boards, row by row.
+    boards: Vec<Game>,
+    /// Which boards are won, and by whom.
+    meta: Game,
+    first: Player,
+    history: Vec<Move>,
+    outcome: Outcome,
+}
+
+impl Default for Ultimate {
+    fn default() -> Self {
+        Ultimate::new()
+    }
+}
+
+impl Ultimate {
+    pub fn new() -> Ultimate {
+        Ultimate::starting_with(Player::X)
+    }
+
+    pub fn starting_with(first: Player) -> Ultimate {
+        let board = Game::with_rules(Rules::tic_tac_toe(), first);
+        Ultimate {
+            boards: vec![board; 9],
+            meta: Game::with_rules(Rules::tic_tac_toe(), first),
+            first,
+            history: Vec::new(),
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    /// The small board at `board` of the grid.
+    pub fn board(&self, board: Move) -> &Game {
+        &self.boards[board.row * 3 + board.col]
+    }
+
+    /// The grid of boards, a square taken by whoever won that board.
+    pub fn meta(&self) -> &Game {
+        &self.meta
+    }
+
+    pub fn get(&self, square: Move) -> Option<Player> {
+        let (board, within) = split(square);
+        self.board(board).get(within)
+    }
+
+    /// The player who made the first move.
+    pub fn first(&self) -> Player {
+        self.first
+    }
+
+    /// The player whose turn it is.
+    pub fn to_move(&self) -> Player {
+        if self.history.len().is_multiple_of(2) {
+            self.first
+        } else {
+            self.first.other()
+        }
+    }
+
+    /// Moves played so far, oldest first.
+    pub fn history(&self) -> &[Move] {
+        &self.history
+    }
+
+    pub fn last_move(&self) -> Option<Move> {
+        self.history.last().copied()
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    /// The board the next move must go in, `None` when any open board will
+    /// do.
+    pub fn next_board(&self) -> Option<Move> {
+        let (_, within) = split(self.last_move()?);
+        if self.board(within).outcome() == Outcome::Ongoing {
+            Some(within)
+        } else {
+            None
+        }
+    }
+
+    /// Every square the next move may go in, none once the game is over.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.outcome != Outcome::Ongoing {
+            return Vec::new();
+        }
+        let next = self.next_board();
+        (0..9)
+            .flat_map(|row| (0..9).map(move |col| Move::new(row, col)))
+            .filter(|&square| {
+                let (board, _) = split(square);
+                next.is_none_or(|next| next == board)
+                    && self.board(board).outcome() == Outcome::Ongoing
+                    && self.get(square).is_none()
+            })
+            .collect()
+    }
+
+    /// Plays `square` for the player to move.
+    pub fn apply(&mut self, square: Move) -> Result<Outcome, MoveError> {
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+        if square.row >= 9 || square.col >= 9 {
+            return Err(MoveError::OutOfRange(square));
+        }
+        let (board, within) = split(square);
+        match self.next_board() {
+            Some(next) if next != board => return Err(MoveError::WrongBoard(keypad_digit(next))),
+            _ => (),
+        }
+        if self.board(board).outcome() != Outcome::Ongoing {
+            return Err(MoveError::BoardDecided(keypad_digit(board)));
+        }
+
+        let player = self.to_move();
+        let small = &mut self.boards[board.row * 3 + board.col];
+        // Named on the whole grid, the way the board is shown
+        let result = small.place(within, player).map_err(|error| match error {
+            MoveError::Occupied(_) => MoveError::Occupied(square),
+            error => error,
+        })?;
+        self.history.push(square);
+        // The grid is won the same way as a small board, by placing the
+        // winner of each small board on it
+        if let Outcome::Win(winner) = result {
+            self.outcome = self.meta.place(board, winner)?;
+        }
+        let decided = self.boards.iter().all(|b| b.outcome() != Outcome::Ongoing);
+        if self.outcome == Outcome::Ongoing && decided {
+            self.outcome = Outcome::Draw;
+        }
+        Ok(self.outcome)
+    }
+}
+
+/// The board of `square` and the square within it.
+fn split(square: Move) -> (Move, Move) {
+    (
+        Move::new(square.row / 3, square.col / 3),
+        Move::new(square.row % 3, square.col % 3),
+    )
+}
+
+/// The keypad key of a square of a 3x3 board, 7 being the top left.
+fn keypad_digit(square: Move) -> usize {
+    (2 - square.row) * 3 + square.col + 1
+}
+
+/// A square as typed in: its board then the square within it.
+fn name(square: Move) -> String {
+    let (board, within) = split(square);
+    format!("{} {}", keypad_digit(board), keypad_digit(within))
+}
+
+impl Display for Ultimate {
+    /// Won boards are filled with the winner's mark, free squares where the
+    /// next move may go are dotted.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let open = self.legal_moves();
+        writeln!(f, "   a b c   d e f   g h i")?;
+        for row in 0..9 {
+            if row == 3 || row == 6 {
+                writeln!(f, "   ------+-------+------")?;
+            }
+            let mut line = format!("{} ", row + 1);
+            for col in 0..9 {
+                let square = Move::new(row, col);
+                let (board, _) = split(square);
+                let mark = match (self.board(board).winner(), self.get(square)) {
+                    (Some(winner), _) | (None, Some(winner)) => winner.symbol(),
+                    (None, None) if open.contains(&square) => '.',
+                    (None, None) => ' ',
+                };
+                if col == 3 || col == 6 {
+                    line.push_str(" |");
+                }
+                line.push(' ');
+                line.push(mark);
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays ultimate tic-tac-toe in the terminal until stdin is closed, one
+/// human playing both sides.
+pub fn run() {
+    println!("\n\n\n\t\t\tULTIMATE TIC TAC TOE YO");
+    println!("Boards and the squares within them are numbered like a keypad:");
+    println!("\t7 | 8 | 9\n\t4 | 5 | 6\n\t1 | 2 | 3");
+    println!("To play, enter the board then the square, e.g. '5 9' for the top right");
+    println!("square of the middle board, or just the square when the board is set.");
+    println!("Squares of the whole grid work too, column letter then row, e.g. 'i1'");
+
+    let mut score = Score::default();
+    let mut game = Ultimate::new();
+    loop {
+        println!("\n{}", score);
+        println!(
+            "Game: {}, round: {}",
+            score.games() + 1,
+            game.history().len() + 1
+        );
+        print!("{}", game);
+        match game.next_board() {
+            Some(board) => println!(
+                "{}'s turn to play in board {}",
+                game.to_move(),
+                keypad_digit(board)
+            ),
+            None => println!("{}'s turn to play in any open board", game.to_move()),
+        }
+
+        // Read input, stop once stdin is closed
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => (),
+        }
+        let square = match parse_ultimate_move(&input, game.next_board()) {
+            Ok(square) => square,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+
+        let player = game.to_move();
+        match game.apply(square) {
+            Ok(Outcome::Ongoing) => println!("{} plays {}", player, name(square)),
+            Ok(outcome) => {
+                print!("\n{}", game);
+                match outcome {
+                    Outcome::Win(player) => println!("Winner: {}", player),
+                    _ => println!("Match is a draw"),
+                }
+                score.record(outcome);
+                game = Ultimate::starting_with(game.first().other());
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+    println!("\nSession over\n{}", score);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rng::Rng;
+    use super::*;
+
+    /// Plays random legal moves until `stop` holds or the game ends.
+    fn random_game(seed: u64, stop: impl Fn(&Ultimate) -> bool) -> Ultimate {
+        let mut rng = Rng::new(seed);
+        let mut game = Ultimate::new();
+        while game.outcome() == Outcome::Ongoing && !stop(&game) {
+            let square = rng.choose(&game.legal_moves()).unwrap();
+            game.apply(square).unwrap();
+        }
+        game
+    }
+
+    fn decided(game: &Ultimate) -> usize {
+        let boards = (0..9).map(|i| Move::new(i / 3, i % 3));
+        boards
+            .filter(|&board| game.board(board).outcome() != Outcome::Ongoing)
+            .count()
+    }
+
+    #[test]
+    fn each_move_picks_the_next_board() {
+        for i in 0..9 {
+            let within = Move::new(i / 3, i % 3);
+            let mut game = Ultimate::new();
+            game.apply(Move::new(3 + within.row, 3 + within.col))
+                .unwrap();
+            assert_eq!(game.next_board(), Some(within));
+            assert!(game
+                .legal_moves()
+                .iter()
+                .all(|&square| split(square).0 == within));
+
+            let elsewhere = Move::new((within.row + 1) % 3, within.col);
+            let wrong = Move::new(elsewhere.row * 3, elsewhere.col * 3);
+            assert_eq!(
+                game.apply(wrong),
+                Err(MoveError::WrongBoard(keypad_digit(within)))
+            );
+        }
+    }
+
+    #[test]
+    fn names_occupied_squares_on_the_whole_grid() {
+        let mut game = Ultimate::new();
+        for input in ["5 7", "7 5"] {
+            let square = parse_ultimate_move(input, game.next_board()).unwrap();
+            game.apply(square).unwrap();
+        }
+        let square = parse_ultimate_move("5 7", game.next_board()).unwrap();
+        assert_eq!(
+            game.apply(square),
+            Err(MoveError::Occupied(Move::new(3, 3)))
+        );
+    }
+
+    #[test]
+    fn a_decided_board_frees_the_next_move() {
+        for seed in 0..20 {
+            let sent_to_decided =
+                |game: &Ultimate| game.last_move().is_some() && game.next_board().is_none();
+            let game = random_game(seed, sent_to_decided);
+            if game.outcome() != Outcome::Ongoing {
+                continue;
+            }
+            let (_, within) = split(game.last_move().unwrap());
+            assert_ne!(game.board(within).outcome(), Outcome::Ongoing);
+            let moves = game.legal_moves();
+            assert!(moves
+                .iter()
+                .any(|&square| split(square).0 != split(moves[0]).0));
+            assert!(moves
+                .iter()
+                .all(|&square| game.board(split(square).0).outcome() == Outcome::Ongoing));
+            return;
+        }
+        panic!("no game sent a player to a decided board");
+    }
+
+    #[test]
+    fn three_boards_in_a_row_win() {
+        let game = (0..)
+            .map(|seed| random_game(seed, |_| false))
+            .find(|game| matches!(game.outcome(), Outcome::Win(_)))
+            .unwrap();
+        let winner = game.meta().winner();
+        assert_eq!(game.outcome(), Outcome::Win(winner.unwrap()));
+        let (board, _) = split(game.last_move().unwrap());
+        assert!(game.meta().winning_line(board).is_some());
+
+        let mut over = game.clone();
+        let square = Move::new(0, 0);
+        assert_eq!(over.apply(square), Err(MoveError::GameOver));
+    }
+
+    #[test]
+    fn every_board_decided_without_a_line_is_a_draw() {
+        let game = (0..)
+            .map(|seed| random_game(seed, |_| false))
+            .find(|game| game.outcome() == Outcome::Draw)
+            .unwrap();
+        assert_eq!(decided(&game), 9);
+        assert_eq!(game.meta().winner(), None);
+        assert!(game.legal_moves().is_empty());
+    }
+}